@@ -1,13 +1,19 @@
 use crate::events::Key;
+use crate::gestures::GestureRecognizer;
+use crate::input_buffer::InputBuffer;
 use crate::math::{vec2, Vec2};
 use crate::mesh::Mesh;
-use crate::shape_pipeline::{BufferedObjectID, MeshBuffer, SSRObjectInfo};
-use crate::{map_present_modes, GransealGameConfig, NGRenderPipeline};
+use crate::post_process::PostEffect;
+use crate::shape_pipeline::{
+    mesh_depth, mesh_translucent, BlendMode, BufferedObjectID, MeshBuffer, SSRObjectInfo, Tag,
+};
+use crate::util::{Color, Rectangle};
+use crate::{map_present_modes, GransealGameConfig, NGRenderPipeline, MSAA};
 use image::EncodableLayout;
 use pollster::FutureExt;
 use std::any::Any;
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use wgpu::util::{DeviceExt, TextureDataOrder};
 use winit::dpi::{LogicalSize, PhysicalSize};
@@ -26,6 +32,12 @@ pub enum NGError {
     SurfaceError(wgpu::SurfaceError),
     ImageError(image::ImageError),
     TextureOverload,
+    UnsupportedFile(PathBuf),
+    ShaderError(String),
+    /// update_image got an area outside the image or the wrong amount of pixel data.
+    InvalidImageUpdate(String),
+    IoError(std::io::Error),
+    MapError(String),
 }
 impl From<wgpu::CreateSurfaceError> for NGError {
     fn from(value: wgpu::CreateSurfaceError) -> Self {
@@ -52,13 +64,30 @@ impl From<wgpu::SurfaceError> for NGError {
         NGError::SurfaceError(e)
     }
 }
+impl From<std::io::Error> for NGError {
+    fn from(e: std::io::Error) -> Self {
+        NGError::IoError(e)
+    }
+}
+impl From<serde_json::Error> for NGError {
+    fn from(e: serde_json::Error) -> Self {
+        NGError::MapError(e.to_string())
+    }
+}
+impl From<roxmltree::Error> for NGError {
+    fn from(e: roxmltree::Error) -> Self {
+        NGError::MapError(e.to_string())
+    }
+}
 pub enum NGCommand {
     AddPipeline(Box<dyn NGRenderPipeline>),
     Render(usize, Box<dyn Any>),
     SetCursorVisibility(bool),
+    SetCursorGrab(bool),
     SetTitle(String),
     CustomEvent(Box<dyn Any>),
     RenderImage(usize, Box<dyn Any>, Image, bool),
+    RenderTarget(usize, Box<dyn Any>, Image, Option<Color>),
 }
 
 pub struct MouseState {
@@ -66,11 +95,19 @@ pub struct MouseState {
     pub left: bool,
     pub right: bool,
     pub middle: bool,
+    pub inside: bool,
+    /// Raw motion accumulated since the last update, keeps working while the cursor is grabbed.
+    pub motion: Vec2,
 }
 // It's so common to check if buttons are held down, let's add that right in.
 pub struct EngineState {
     pub mouse: MouseState,
     pub fps: i32,
+    pub focused: bool,
+    pub occluded: bool,
+    pub scale_factor: f64,
+    /// Active touches by id, with their current position.
+    pub touches: HashMap<u64, Vec2>,
     pub(crate) keys: HashMap<Key, bool>,
 }
 impl Default for EngineState {
@@ -81,8 +118,14 @@ impl Default for EngineState {
                 left: false,
                 right: false,
                 middle: false,
+                inside: false,
+                motion: Vec2::ZERO,
             },
             fps: 0,
+            focused: true,
+            occluded: false,
+            scale_factor: 1.0,
+            touches: HashMap::new(),
             keys: HashMap::new(),
         }
     }
@@ -92,6 +135,18 @@ pub struct TextureInfo {
     pub(crate) texture: wgpu::Texture,
     pub(crate) bind_group_layout: wgpu::BindGroupLayout,
     pub(crate) bind_group: wgpu::BindGroup,
+    pub(crate) view: wgpu::TextureView,
+    pub(crate) sampler: wgpu::Sampler,
+    pub(crate) target: Option<RenderTarget>,
+    pub(crate) destroyed: bool,
+    /// Has pixels that aren't fully opaque, objects using it skip the depth pre-pass.
+    pub(crate) translucent: bool,
+}
+
+/// The attachments that belong to an image made with NGCore::create_render_target.
+pub(crate) struct RenderTarget {
+    pub(crate) depth: wgpu::Texture,
+    pub(crate) msaa: Option<wgpu::Texture>,
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -101,7 +156,133 @@ pub struct Image {
     size: Vec2,
     pub sub_image: Option<(Vec2, Vec2)>,
 }
+/// How an image is sampled, passed anywhere an image is loaded. true and false still mean nearest
+/// and linear filtering. Address modes only matter for UVs outside 0..1, like a sub_image reaching
+/// past the image, which repeats it with AddressMode::Repeat.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageOptions {
+    pub address_u: wgpu::AddressMode,
+    pub address_v: wgpu::AddressMode,
+    /// Used with AddressMode::ClampToBorder, which falls back to ClampToEdge on devices without it.
+    pub border_color: wgpu::SamplerBorderColor,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    /// 1 to 16, anything above 1 needs every filter to be linear and is ignored otherwise.
+    pub anisotropy: u16,
+    /// Generate a full mip chain on upload, so minified images stop shimmering.
+    pub mipmaps: bool,
+}
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            address_u: wgpu::AddressMode::ClampToEdge,
+            address_v: wgpu::AddressMode::ClampToEdge,
+            border_color: wgpu::SamplerBorderColor::TransparentBlack,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+            mipmaps: false,
+        }
+    }
+}
+impl From<bool> for ImageOptions {
+    fn from(nearest: bool) -> Self {
+        if nearest {
+            Self::nearest()
+        } else {
+            Self::default()
+        }
+    }
+}
+impl ImageOptions {
+    /// Nearest magnification for pixel art, minified images are still smoothed.
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        }
+    }
+    pub fn address(mut self, mode: wgpu::AddressMode) -> Self {
+        self.address_u = mode;
+        self.address_v = mode;
+        self
+    }
+    pub fn address_uv(mut self, u: wgpu::AddressMode, v: wgpu::AddressMode) -> Self {
+        self.address_u = u;
+        self.address_v = v;
+        self
+    }
+    pub fn border(mut self, color: wgpu::SamplerBorderColor) -> Self {
+        self = self.address(wgpu::AddressMode::ClampToBorder);
+        self.border_color = color;
+        self
+    }
+    pub fn filter(mut self, mag: wgpu::FilterMode, min: wgpu::FilterMode) -> Self {
+        self.mag_filter = mag;
+        self.min_filter = min;
+        self
+    }
+    pub fn anisotropy(mut self, anisotropy: u16) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+    pub fn mipmaps(mut self, mipmaps: bool) -> Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+    fn sampler_descriptor(&self, border: bool) -> wgpu::SamplerDescriptor<'static> {
+        let address = |mode| match mode {
+            wgpu::AddressMode::ClampToBorder if !border => wgpu::AddressMode::ClampToEdge,
+            mode => mode,
+        };
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&filter| filter == wgpu::FilterMode::Linear);
+        let (address_u, address_v) = (address(self.address_u), address(self.address_v));
+        wgpu::SamplerDescriptor {
+            label: Some("Texture Sampler"),
+            address_mode_u: address_u,
+            address_mode_v: address_v,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: if self.mipmaps { 32.0 } else { 0.0 },
+            compare: None,
+            anisotropy_clamp: if linear {
+                self.anisotropy.clamp(1, 16)
+            } else {
+                1
+            },
+            border_color: [address_u, address_v]
+                .contains(&wgpu::AddressMode::ClampToBorder)
+                .then_some(self.border_color),
+        }
+    }
+}
+
+/// Each level halves the one before it, down to 1x1.
+fn mip_chain(width: u32, height: u32, data: &[u8]) -> Vec<Vec<u8>> {
+    let mut levels = vec![data.to_vec()];
+    let mut image = match image::RgbaImage::from_raw(width, height, data.to_vec()) {
+        Some(image) => image,
+        None => return levels,
+    };
+    while image.width() > 1 || image.height() > 1 {
+        let (w, h) = ((image.width() / 2).max(1), (image.height() / 2).max(1));
+        image = image::imageops::resize(&image, w, h, image::imageops::FilterType::Triangle);
+        levels.push(image.as_raw().clone());
+    }
+    levels
+}
+
 pub const TEXTURE_SIZE: u32 = 8192;
+/// The most textures a single draw can pick from on devices with texture binding arrays.
+pub const MAX_BINDLESS_TEXTURES: u32 = 1024;
 impl Image {
     pub fn atlas_id(&self) -> Option<usize> {
         if let Some(atlas) = self.atlas {
@@ -144,6 +325,12 @@ impl Image {
     }
 }
 
+/// A custom fragment shader registered with NGCore::create_material.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Material {
+    pub(crate) id: usize,
+}
+
 pub struct NGCore {
     pub config: GransealGameConfig,
     pub timer: std::time::Instant,
@@ -156,9 +343,21 @@ pub struct NGCore {
     pub queue: wgpu::Queue,
     pub(crate) cmd_queue: Vec<NGCommand>,
     pub state: EngineState,
+    pub gestures: GestureRecognizer,
+    pub inputs: InputBuffer,
+    /// Applied in order to every frame before presenting, change them at any time.
+    pub post_effects: Vec<PostEffect>,
     pub(crate) mesh_buffers: Vec<MeshBuffer>,
-    pub(crate) buffered_objects: Vec<SSRObjectInfo>,
+    pub(crate) buffered_objects: Vec<Vec<SSRObjectInfo>>,
     pub(crate) textures: Vec<TextureInfo>,
+    /// Bumped whenever a texture is added or destroyed, so texture binding arrays get rebuilt.
+    pub(crate) texture_generation: usize,
+    /// How many textures a binding array can hold, None when the device can't index them per draw.
+    pub(crate) bindless: Option<u32>,
+    pub(crate) materials: Vec<wgpu::ShaderModule>,
+    /// Where the renderer should look next for NGCore::pick, and what the last pick found.
+    pub(crate) pick_request: Option<Vec2>,
+    pub(crate) picked: Option<Tag>,
 }
 
 impl NGCore {
@@ -168,21 +367,26 @@ impl NGCore {
         let data = image.as_bytes();
         self.load_image_data(image.width(), image.height(), data, true);
     }
-    pub fn load_image_from_memory(&mut self, data: &[u8], nearest: bool) -> Result<Image, NGError> {
+    pub fn load_image_from_memory(
+        &mut self,
+        data: &[u8],
+        options: impl Into<ImageOptions>,
+    ) -> Result<Image, NGError> {
         let image = image::load_from_memory(data)?.to_rgba8();
-        Ok(self.load_image_data(image.width(), image.height(), image.as_bytes(), nearest))
+        Ok(self.load_image_data(image.width(), image.height(), image.as_bytes(), options))
     }
     pub fn load_image_data(
         &mut self,
         width: u32,
         height: u32,
         data: &[u8],
-        nearest: bool,
+        options: impl Into<ImageOptions>,
     ) -> Image {
-        let filter_mode = if nearest {
-            wgpu::FilterMode::Nearest
+        let options = options.into();
+        let levels = if options.mipmaps {
+            mip_chain(width, height, data)
         } else {
-            wgpu::FilterMode::Linear
+            vec![data.to_vec()]
         };
         let tex = wgpu::TextureDescriptor {
             label: Some("Image Texture"),
@@ -191,7 +395,7 @@ impl NGCore {
                 height,
                 depth_or_array_layers: 1,
             },
-            mip_level_count: 1,
+            mip_level_count: levels.len() as u32,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Rgba8UnormSrgb,
@@ -204,23 +408,27 @@ impl NGCore {
             &self.queue,
             &tex,
             TextureDataOrder::LayerMajor,
-            data,
+            &levels.concat(),
         );
+        let translucent = data.chunks_exact(4).any(|pixel| pixel[3] < u8::MAX);
+        self.add_texture(texture, options, None, translucent)
+    }
+    fn add_texture(
+        &mut self,
+        texture: wgpu::Texture,
+        options: ImageOptions,
+        target: Option<RenderTarget>,
+        translucent: bool,
+    ) -> Image {
+        let (width, height) = (texture.width(), texture.height());
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = self.device.create_sampler(&wgpu::SamplerDescriptor {
-            label: Some("Texture Sampler"),
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: filter_mode,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: filter_mode,
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 0.0,
-            compare: None,
-            anisotropy_clamp: 1,
-            border_color: None,
-        });
+        let border = self
+            .device
+            .features()
+            .contains(wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER);
+        let sampler = self
+            .device
+            .create_sampler(&options.sampler_descriptor(border));
         let bind_group_layout =
             self.device
                 .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -262,7 +470,13 @@ impl NGCore {
             texture,
             bind_group_layout,
             bind_group,
+            view,
+            sampler,
+            target,
+            destroyed: false,
+            translucent,
         });
+        self.texture_generation += 1;
         Image {
             texture: self.textures.len() - 1,
             size: Vec2::new(width, height),
@@ -270,11 +484,160 @@ impl NGCore {
             sub_image: None,
         }
     }
+    /// An image that can be drawn into with ShapeGfx::begin_target and then drawn like any other.
+    /// It gets its own depth buffer, and MSAA attachment when msaa isn't disabled.
+    pub fn create_render_target(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        msaa: MSAA,
+    ) -> Image {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let sample_count = match msaa {
+            MSAA::Disabled => 1,
+            MSAA::Enable4x => 4,
+            MSAA::Enable8x => 8,
+        };
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Render Target Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::COPY_SRC
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Render Target Depth Stencil"),
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32FloatStencil8,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let msaa = (sample_count > 1).then(|| {
+            self.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Render Target MultiSample"),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+        });
+        self.add_texture(
+            texture,
+            ImageOptions::default(),
+            Some(RenderTarget { depth, msaa }),
+            true,
+        )
+    }
+    /// Replace the pixels in rect, in pixels from the image's top left, with tightly packed rows
+    /// in the texture's format, RGBA8 for loaded images. Images created with mipmaps can't be
+    /// updated, their smaller levels would keep the old pixels.
+    pub fn update_image(
+        &mut self,
+        image: &Image,
+        rect: Rectangle,
+        data: &[u8],
+    ) -> Result<(), NGError> {
+        let info = match self
+            .textures
+            .get_mut(image.texture_id().unwrap_or(image.texture))
+        {
+            Some(info) if !info.destroyed => info,
+            _ => {
+                return Err(NGError::InvalidImageUpdate(
+                    "The image was destroyed".into(),
+                ))
+            }
+        };
+        if info.texture.mip_level_count() > 1 {
+            return Err(NGError::InvalidImageUpdate(
+                "Images with mipmaps can't be updated".into(),
+            ));
+        }
+        let offset = image.atlas.map_or(Vec2::ZERO, |(_, pos)| pos)
+            + image.sub_image.map_or(Vec2::ZERO, |(start, _)| start);
+        let (x, y) = (rect.top_left.x.round(), rect.top_left.y.round());
+        let (width, height) = (rect.size().x.round() as u32, rect.size().y.round() as u32);
+        if x < 0.0
+            || y < 0.0
+            || x + width as f32 > image.size().x
+            || y + height as f32 > image.size().y
+        {
+            return Err(NGError::InvalidImageUpdate(format!(
+                "{rect:?} isn't inside the {:?} image",
+                image.size()
+            )));
+        }
+        let format = info.texture.format();
+        let pixel_size = format.block_copy_size(None).unwrap_or(4);
+        let expected = (width * height * pixel_size) as usize;
+        if data.len() != expected {
+            return Err(NGError::InvalidImageUpdate(format!(
+                "Expected {expected} bytes, got {}",
+                data.len()
+            )));
+        }
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let origin = offset + vec2(x, y);
+        self.queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &info.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: origin.x as u32,
+                    y: origin.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * pixel_size),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        if info.target.is_none() && format == wgpu::TextureFormat::Rgba8UnormSrgb {
+            let translucent = data.chunks_exact(4).any(|pixel| pixel[3] < u8::MAX);
+            let whole = (width, height) == (info.texture.width(), info.texture.height());
+            info.translucent = translucent || (info.translucent && !whole);
+        }
+        Ok(())
+    }
     //TODO Make this an NGCommand so it does it later, after other things are done.
     pub fn destroy_image(&mut self, image: &Image) {
         self.textures[image.texture].texture.destroy();
+        self.textures[image.texture].destroyed = true;
+        self.texture_generation += 1;
     }
-    pub fn load_image<P>(&mut self, file: P, nearest: bool) -> Result<Image, NGError>
+    pub fn load_image<P>(
+        &mut self,
+        file: P,
+        options: impl Into<ImageOptions>,
+    ) -> Result<Image, NGError>
     where
         P: AsRef<Path>,
     {
@@ -283,16 +646,37 @@ impl NGCore {
             Err(err) => return Err(NGError::ImageError(err)),
         };
         let data = image.as_raw().as_slice();
-        Ok(self.load_image_data(image.width(), image.height(), data, nearest))
+        Ok(self.load_image_data(image.width(), image.height(), data, options))
+    }
+    /// Load a file dropped onto the window, see Event::FileDropped.
+    /// Files that aren't a known image format are rejected before trying to decode them.
+    pub fn load_dropped_image<P>(
+        &mut self,
+        file: P,
+        options: impl Into<ImageOptions>,
+    ) -> Result<Image, NGError>
+    where
+        P: AsRef<Path>,
+    {
+        let path = file.as_ref();
+        match image::ImageFormat::from_path(path) {
+            Ok(_) => self.load_image(path, options),
+            Err(_) => Err(NGError::UnsupportedFile(path.to_path_buf())),
+        }
     }
-    pub fn create_image(&mut self, width: u32, height: u32, nearest: bool) -> Image {
+    pub fn create_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        options: impl Into<ImageOptions>,
+    ) -> Image {
         let mut image = image::RgbaImage::new(width, height);
         image.fill(u8::MAX);
         self.load_image_data(
             image.width(),
             image.height(),
             image.as_raw().as_slice(),
-            nearest,
+            options,
         )
     }
     pub fn update_buffer_object(&mut self, slot: usize, mesh: &Mesh) -> bool {
@@ -317,10 +701,10 @@ impl NGCore {
                 });
             bo.vertex_buffer = vertex_buffer;
             bo.index_buffer = index_buffer;
-            bo.texture = match mesh.image {
-                Some(image) => Some(image.texture),
-                None => None,
-            };
+            bo.texture = mesh.get_texture_id();
+            if let Some(parts) = self.buffered_objects.get_mut(slot) {
+                *parts = buffered_parts(slot, mesh);
+            }
             true
         } else {
             false
@@ -342,21 +726,35 @@ impl NGCore {
                 usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
             });
         let bo_slot = self.mesh_buffers.len();
-        let object_info = SSRObjectInfo {
-            bo_slot: Some(bo_slot),
-            start_vertice: 0,
-            start_index: 0,
-            end_index: 0,
-            texture: mesh.get_texture_id(),
-        };
         self.mesh_buffers.push(MeshBuffer {
             vertex_buffer,
             index_buffer,
             texture: mesh.get_texture_id(),
         });
-        self.buffered_objects.push(object_info);
+        self.buffered_objects.push(buffered_parts(bo_slot, mesh));
         self.buffered_objects.len() - 1
     }
+    /// Compile a custom fragment shader for ShapeGfx::set_material.
+    /// The source is appended to the engine's shader, so it can use VertexOutput, tex, samp, timer
+    /// and material_params(in), and must provide `@fragment fn fs_main(in: VertexOutput) -> @location(0) vec4<f32>`.
+    /// With the Multiply and Screen blend modes fs_main should return color premultiplied by alpha.
+    pub fn create_material(&mut self, fragment: &str) -> Result<Material, NGError> {
+        let source = format!("{}\n{}", include_str!("shape_shader.wgsl"), fragment);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("SSR Material Shader"),
+                source: wgpu::ShaderSource::Wgsl(source.into()),
+            });
+        if let Some(err) = self.device.pop_error_scope().block_on() {
+            return Err(NGError::ShaderError(err.to_string()));
+        }
+        self.materials.push(module);
+        Ok(Material {
+            id: self.materials.len() - 1,
+        })
+    }
     pub fn key_held(&self, key: Key) -> bool {
         if !self.state.keys.contains_key(&key) {
             false
@@ -364,9 +762,26 @@ impl NGCore {
             self.state.keys[&key]
         }
     }
+    /// What's drawn on screen at pos, in pixels. The ID pass only runs while picks are asked for
+    /// and is read back without stalling, so this returns the answer to an earlier call, usually
+    /// from a frame or two ago. Call it every frame, with the mouse position for hovering.
+    pub fn pick(&mut self, pos: Vec2) -> Option<Tag> {
+        self.pick_request = Some(pos);
+        self.picked
+    }
     pub fn render(&mut self, pipeline: usize, data: Box<dyn Any>) {
         self.cmd_queue.push(NGCommand::Render(pipeline, data));
     }
+    pub fn render_target(
+        &mut self,
+        pipeline: usize,
+        data: Box<dyn Any>,
+        target: &Image,
+        clear: Option<Color>,
+    ) {
+        self.cmd_queue
+            .push(NGCommand::RenderTarget(pipeline, data, *target, clear));
+    }
     pub fn render_image(
         &mut self,
         pipeline: usize,
@@ -393,6 +808,11 @@ impl NGCore {
     pub fn set_cursor_visibility(&mut self, visible: bool) {
         self.cmd_queue.push(NGCommand::SetCursorVisibility(visible))
     }
+    /// Lock (or confine, where locking isn't supported) the cursor to the window.
+    /// Use Event::MouseMotion for relative movement while grabbed.
+    pub fn set_cursor_grab(&mut self, grab: bool) {
+        self.cmd_queue.push(NGCommand::SetCursorGrab(grab))
+    }
     pub fn new(
         event_loop: &EventLoop<()>,
         mut config: GransealGameConfig,
@@ -453,6 +873,26 @@ impl NGCore {
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: vec![caps.formats[0]],
         };
+        let bindless_features = wgpu::Features::TEXTURE_BINDING_ARRAY
+            | wgpu::Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING;
+        let adapter_limits = adapter.limits();
+        // One texture and sampler per stage are taken by the regular texture bind group.
+        let bindless = Some(
+            adapter_limits
+                .max_sampled_textures_per_shader_stage
+                .min(adapter_limits.max_samplers_per_shader_stage)
+                .min(MAX_BINDLESS_TEXTURES + 1)
+                .saturating_sub(1),
+        )
+        .filter(|&count| count >= 64 && adapter.features().contains(bindless_features));
+        let required_limits = match bindless {
+            Some(count) => wgpu::Limits {
+                max_sampled_textures_per_shader_stage: count + 1,
+                max_samplers_per_shader_stage: count + 1,
+                ..Default::default()
+            },
+            None => Default::default(),
+        };
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
@@ -460,14 +900,23 @@ impl NGCore {
                     required_features: wgpu::Features::STORAGE_RESOURCE_BINDING_ARRAY
                         | wgpu::Features::BUFFER_BINDING_ARRAY
                         | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
-                        | wgpu::Features::DEPTH32FLOAT_STENCIL8,
-                    required_limits: Default::default(),
+                        | wgpu::Features::DEPTH32FLOAT_STENCIL8
+                        | (adapter.features() & wgpu::Features::ADDRESS_MODE_CLAMP_TO_BORDER)
+                        | if bindless.is_some() {
+                            bindless_features
+                        } else {
+                            wgpu::Features::empty()
+                        },
+                    required_limits,
                 },
                 None,
             )
             .block_on()?;
         surface.configure(&device, &surface_configuration);
-        let state = EngineState::default();
+        let state = EngineState {
+            scale_factor: window.scale_factor(),
+            ..Default::default()
+        };
 
         // change config to whatever size we actually ended up with.
         let size = window.inner_size();
@@ -487,11 +936,72 @@ impl NGCore {
             queue,
             cmd_queue: vec![],
             state,
+            gestures: GestureRecognizer::new(),
+            inputs: InputBuffer::new(),
             mesh_buffers: vec![],
             buffered_objects: vec![],
             textures: vec![],
+            texture_generation: 0,
+            bindless,
+            materials: vec![],
+            post_effects: vec![],
+            pick_request: None,
+            picked: None,
         };
         core.initialize_texture();
         Ok(core)
     }
 }
+
+/// One object per texture part of a buffered mesh, drawing that range of its index buffer.
+fn buffered_parts(bo_slot: usize, mesh: &Mesh) -> Vec<SSRObjectInfo> {
+    mesh.texture_parts()
+        .into_iter()
+        .map(|(range, image)| SSRObjectInfo {
+            bo_slot: Some(bo_slot),
+            start_vertice: 0,
+            end_vertice: 0,
+            depth: mesh_depth(&mesh.vertices),
+            translucent: mesh_translucent(&mesh.vertices),
+            start_index: range.start,
+            end_index: range.end,
+            texture: image.and_then(|image| image.texture_id()),
+            blend: BlendMode::default(),
+            material: None,
+            stencil: Default::default(),
+            stencil_ref: 0,
+            clip: None,
+            bindless: false,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_options() {
+        let sampler = ImageOptions::nearest()
+            .anisotropy(8)
+            .sampler_descriptor(true);
+        assert_eq!(sampler.anisotropy_clamp, 1);
+        assert_eq!(sampler.lod_max_clamp, 0.0);
+        let options = ImageOptions::default()
+            .border(wgpu::SamplerBorderColor::OpaqueBlack)
+            .anisotropy(32)
+            .mipmaps(true);
+        let sampler = options.sampler_descriptor(true);
+        assert_eq!(sampler.anisotropy_clamp, 16);
+        assert_eq!(
+            sampler.border_color,
+            Some(wgpu::SamplerBorderColor::OpaqueBlack)
+        );
+        let sampler = options.sampler_descriptor(false);
+        assert_eq!(sampler.address_mode_u, wgpu::AddressMode::ClampToEdge);
+        assert_eq!(sampler.border_color, None);
+        let levels = mip_chain(4, 2, &[255; 4 * 2 * 4]);
+        let sizes = levels.iter().map(|l| l.len() / 4).collect::<Vec<_>>();
+        assert_eq!(sizes, [8, 2, 1]);
+    }
+}
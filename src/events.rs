@@ -1,5 +1,8 @@
+use crate::gestures::Gesture;
+use crate::math::{vec2, Vec2};
+use std::path::PathBuf;
 use std::time::Duration;
-use winit::event::{MouseScrollDelta, WindowEvent};
+use winit::event::{DeviceEvent, MouseScrollDelta, WindowEvent};
 
 #[derive()]
 pub enum Event {
@@ -13,6 +16,34 @@ pub enum Event {
     },
     MouseMoved(f64, f64),
     MouseWheel(f32, f32),
+    /// Scrolling in pixels, as reported by touchpads and some high resolution wheels.
+    MouseWheelPixels(f64, f64),
+    /// Raw relative mouse motion from the device, still reported while the cursor is grabbed.
+    MouseMotion(f64, f64),
+    MouseEntered,
+    MouseLeft,
+    FocusGained,
+    FocusLost,
+    Occluded(bool),
+    ScaleFactorChanged(f64),
+    Touch {
+        id: u64,
+        phase: TouchPhase,
+        pos: Vec2,
+    },
+    Gesture(Gesture),
+    /// A file is being dragged over the window, pos is the last known cursor position.
+    FileHovered {
+        path: PathBuf,
+        pos: Vec2,
+    },
+    FileHoverCancelled,
+    FileDropped {
+        path: PathBuf,
+        pos: Vec2,
+    },
+    /// A combo registered with NGCore::inputs was completed, carries the combo name.
+    Combo(String),
     Draw,
     Update(Duration),
     Load,
@@ -20,7 +51,7 @@ pub enum Event {
     Custom(Box<dyn std::any::Any>),
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum MouseButton {
     Left,
     Middle,
@@ -30,6 +61,14 @@ pub enum MouseButton {
     Forward,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum KeyState {
     Pressed,
@@ -189,9 +228,27 @@ pub fn map_events(event: &winit::event::WindowEvent) -> Option<Event> {
         }
         winit::event::WindowEvent::MouseWheel { delta, .. } => match delta {
             MouseScrollDelta::LineDelta(x, y) => Some(Event::MouseWheel(*x, *y)),
-            _ => None,
+            MouseScrollDelta::PixelDelta(pos) => Some(Event::MouseWheelPixels(pos.x, pos.y)),
+        },
+        winit::event::WindowEvent::CursorEntered { .. } => Some(Event::MouseEntered),
+        winit::event::WindowEvent::CursorLeft { .. } => Some(Event::MouseLeft),
+        winit::event::WindowEvent::Focused(focused) => match focused {
+            true => Some(Event::FocusGained),
+            false => Some(Event::FocusLost),
         },
+        winit::event::WindowEvent::Occluded(occluded) => Some(Event::Occluded(*occluded)),
+        winit::event::WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+            Some(Event::ScaleFactorChanged(*scale_factor))
+        }
+        winit::event::WindowEvent::Touch(touch) => Some(Event::Touch {
+            id: touch.id,
+            phase: map_touch_phase(&touch.phase),
+            pos: vec2(touch.location.x, touch.location.y),
+        }),
         winit::event::WindowEvent::Resized(size) => Some(Event::Resized(size.width, size.height)),
+        winit::event::WindowEvent::HoveredFile(_)
+        | winit::event::WindowEvent::HoveredFileCancelled
+        | winit::event::WindowEvent::DroppedFile(_) => None, // See map_file_events
         event => {
             match event {
                 WindowEvent::RedrawRequested => {}
@@ -202,7 +259,39 @@ pub fn map_events(event: &winit::event::WindowEvent) -> Option<Event> {
     }
 }
 
-fn map_mouse_buttons(button: &winit::event::MouseButton) -> MouseButton {
+/// winit doesn't report where a file was dropped, so the cursor position is passed in.
+pub fn map_file_events(event: &WindowEvent, pos: Vec2) -> Option<Event> {
+    match event {
+        WindowEvent::HoveredFile(path) => Some(Event::FileHovered {
+            path: path.clone(),
+            pos,
+        }),
+        WindowEvent::HoveredFileCancelled => Some(Event::FileHoverCancelled),
+        WindowEvent::DroppedFile(path) => Some(Event::FileDropped {
+            path: path.clone(),
+            pos,
+        }),
+        _ => None,
+    }
+}
+
+pub fn map_device_events(event: &DeviceEvent) -> Option<Event> {
+    match event {
+        DeviceEvent::MouseMotion { delta } => Some(Event::MouseMotion(delta.0, delta.1)),
+        _ => None,
+    }
+}
+
+pub fn map_touch_phase(phase: &winit::event::TouchPhase) -> TouchPhase {
+    match phase {
+        winit::event::TouchPhase::Started => TouchPhase::Started,
+        winit::event::TouchPhase::Moved => TouchPhase::Moved,
+        winit::event::TouchPhase::Ended => TouchPhase::Ended,
+        winit::event::TouchPhase::Cancelled => TouchPhase::Cancelled,
+    }
+}
+
+pub fn map_mouse_buttons(button: &winit::event::MouseButton) -> MouseButton {
     match button {
         winit::event::MouseButton::Left => MouseButton::Left,
         winit::event::MouseButton::Right => MouseButton::Right,
@@ -213,6 +302,13 @@ fn map_mouse_buttons(button: &winit::event::MouseButton) -> MouseButton {
     }
 }
 
+pub fn map_key_state(state: &winit::event::ElementState) -> KeyState {
+    match state {
+        winit::event::ElementState::Pressed => KeyState::Pressed,
+        winit::event::ElementState::Released => KeyState::Released,
+    }
+}
+
 pub fn map_keys(key: &winit::keyboard::PhysicalKey) -> Key {
     use winit::keyboard::KeyCode::*;
     use winit::keyboard::PhysicalKey::Code as P;
@@ -325,3 +421,27 @@ pub fn map_keys(key: &winit::keyboard::PhysicalKey) -> Key {
         _ => G::NotImplemented,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map_file_events() {
+        let path = PathBuf::from("maps/level.tmj");
+        let pos = vec2(12, 34);
+        assert!(matches!(
+            map_file_events(&WindowEvent::HoveredFile(path.clone()), pos),
+            Some(Event::FileHovered { path: p, pos: at }) if p == path && at == pos
+        ));
+        assert!(matches!(
+            map_file_events(&WindowEvent::DroppedFile(path.clone()), pos),
+            Some(Event::FileDropped { path: p, pos: at }) if p == path && at == pos
+        ));
+        assert!(matches!(
+            map_file_events(&WindowEvent::HoveredFileCancelled, pos),
+            Some(Event::FileHoverCancelled)
+        ));
+        assert!(map_file_events(&WindowEvent::Focused(true), pos).is_none());
+    }
+}
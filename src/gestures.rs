@@ -0,0 +1,315 @@
+use crate::events::TouchPhase;
+use crate::math::Vec2;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Gesture {
+    Tap(Vec2),
+    DoubleTap(Vec2),
+    LongPress(Vec2),
+    /// start, end and velocity in pixels per second.
+    Swipe {
+        start: Vec2,
+        end: Vec2,
+        velocity: Vec2,
+    },
+    /// scale is relative to the previous pinch, multiply it into your zoom.
+    Pinch {
+        center: Vec2,
+        scale: f32,
+    },
+    /// angle in radians, relative to the previous rotate.
+    Rotate {
+        center: Vec2,
+        angle: f32,
+    },
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TouchTrack {
+    start: Vec2,
+    pos: Vec2,
+    time: Instant,
+    long_pressed: bool,
+    multi: bool,
+}
+
+/// Turns raw touch events into gestures. The thresholds are public so they can be tuned per game.
+#[derive(Clone, Debug)]
+pub struct GestureRecognizer {
+    pub tap_time: Duration,
+    pub tap_slop: f32,
+    pub double_tap_time: Duration,
+    pub long_press_time: Duration,
+    pub swipe_time: Duration,
+    pub swipe_distance: f32,
+    pub pinch_threshold: f32,
+    pub rotate_threshold: f32,
+    touches: HashMap<u64, TouchTrack>,
+    last_tap: Option<(Vec2, Instant)>,
+    pair: Option<(f32, f32)>,
+}
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self {
+            tap_time: Duration::from_millis(300),
+            tap_slop: 10.0,
+            double_tap_time: Duration::from_millis(300),
+            long_press_time: Duration::from_millis(500),
+            swipe_time: Duration::from_millis(500),
+            swipe_distance: 50.0,
+            pinch_threshold: 0.01,
+            rotate_threshold: 0.01,
+            touches: HashMap::new(),
+            last_tap: None,
+            pair: None,
+        }
+    }
+}
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn touch_count(&self) -> usize {
+        self.touches.len()
+    }
+    pub fn touch(&mut self, id: u64, phase: TouchPhase, pos: Vec2, now: Instant) -> Vec<Gesture> {
+        let mut gestures = vec![];
+        match phase {
+            TouchPhase::Started => {
+                self.touches.insert(
+                    id,
+                    TouchTrack {
+                        start: pos,
+                        pos,
+                        time: now,
+                        long_pressed: false,
+                        multi: false,
+                    },
+                );
+                if self.touches.len() > 1 {
+                    self.touches.values_mut().for_each(|t| t.multi = true);
+                }
+                self.pair = self.pair_metrics();
+            }
+            TouchPhase::Moved => {
+                if let Some(track) = self.touches.get_mut(&id) {
+                    track.pos = pos;
+                }
+                if let (Some((distance, angle)), Some((center, new_distance, new_angle))) =
+                    (self.pair, self.pair_center())
+                {
+                    let mut pair = (distance, angle);
+                    if distance > 0.0 {
+                        let scale = new_distance / distance;
+                        if (scale - 1.0).abs() >= self.pinch_threshold {
+                            gestures.push(Gesture::Pinch { center, scale });
+                            pair.0 = new_distance;
+                        }
+                    }
+                    let turn = wrap_angle(new_angle - angle);
+                    if turn.abs() >= self.rotate_threshold {
+                        gestures.push(Gesture::Rotate {
+                            center,
+                            angle: turn,
+                        });
+                        pair.1 = new_angle;
+                    }
+                    self.pair = Some(pair);
+                }
+            }
+            TouchPhase::Ended => {
+                if let Some(track) = self.touches.remove(&id) {
+                    if !track.multi && !track.long_pressed {
+                        if let Some(g) = self.single_touch_ended(&track, pos, now) {
+                            gestures.push(g);
+                        }
+                    }
+                }
+                self.pair = self.pair_metrics();
+            }
+            TouchPhase::Cancelled => {
+                self.touches.remove(&id);
+                self.pair = self.pair_metrics();
+            }
+        }
+        gestures
+    }
+    /// Call once per frame, long presses are time based and need to fire while the finger is still down.
+    pub fn update(&mut self, now: Instant) -> Vec<Gesture> {
+        let mut gestures = vec![];
+        if self.touches.len() != 1 {
+            return gestures;
+        }
+        for track in self.touches.values_mut() {
+            if !track.multi
+                && !track.long_pressed
+                && now.duration_since(track.time) >= self.long_press_time
+                && (track.pos - track.start).magnitude() <= self.tap_slop
+            {
+                track.long_pressed = true;
+                gestures.push(Gesture::LongPress(track.pos));
+            }
+        }
+        gestures
+    }
+    fn single_touch_ended(
+        &mut self,
+        track: &TouchTrack,
+        pos: Vec2,
+        now: Instant,
+    ) -> Option<Gesture> {
+        let held = now.duration_since(track.time);
+        let moved = pos - track.start;
+        if held <= self.tap_time && moved.magnitude() <= self.tap_slop {
+            if let Some((last_pos, last_time)) = self.last_tap {
+                if now.duration_since(last_time) <= self.double_tap_time
+                    && (pos - last_pos).magnitude() <= self.tap_slop * 2.0
+                {
+                    self.last_tap = None;
+                    return Some(Gesture::DoubleTap(pos));
+                }
+            }
+            self.last_tap = Some((pos, now));
+            Some(Gesture::Tap(pos))
+        } else if held <= self.swipe_time && moved.magnitude() >= self.swipe_distance {
+            let seconds = held.as_secs_f32().max(f32::EPSILON);
+            Some(Gesture::Swipe {
+                start: track.start,
+                end: pos,
+                velocity: moved / seconds,
+            })
+        } else {
+            None
+        }
+    }
+    fn pair_center(&self) -> Option<(Vec2, f32, f32)> {
+        if self.touches.len() != 2 {
+            return None;
+        }
+        let mut iter = self.touches.values();
+        let a = iter.next()?.pos;
+        let b = iter.next()?.pos;
+        let d = b - a;
+        Some(((a + b) / 2.0, d.magnitude(), d.y.atan2(d.x)))
+    }
+    fn pair_metrics(&self) -> Option<(f32, f32)> {
+        self.pair_center()
+            .map(|(_, distance, angle)| (distance, angle))
+    }
+}
+
+fn wrap_angle(a: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    let mut a = a % TAU;
+    if a > PI {
+        a -= TAU;
+    } else if a < -PI {
+        a += TAU;
+    }
+    a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec2;
+
+    #[test]
+    fn test_tap_and_double_tap() {
+        let mut g = GestureRecognizer::new();
+        let t = Instant::now();
+        g.touch(0, TouchPhase::Started, vec2(10, 10), t);
+        let first = g.touch(
+            0,
+            TouchPhase::Ended,
+            vec2(11, 10),
+            t + Duration::from_millis(50),
+        );
+        assert_eq!(first, vec![Gesture::Tap(vec2(11, 10))]);
+        g.touch(
+            1,
+            TouchPhase::Started,
+            vec2(12, 10),
+            t + Duration::from_millis(150),
+        );
+        let second = g.touch(
+            1,
+            TouchPhase::Ended,
+            vec2(12, 10),
+            t + Duration::from_millis(200),
+        );
+        assert_eq!(second, vec![Gesture::DoubleTap(vec2(12, 10))]);
+    }
+    #[test]
+    fn test_long_press_suppresses_tap() {
+        let mut g = GestureRecognizer::new();
+        let t = Instant::now();
+        g.touch(0, TouchPhase::Started, vec2(10, 10), t);
+        assert!(g.update(t + Duration::from_millis(100)).is_empty());
+        let pressed = g.update(t + Duration::from_millis(600));
+        assert_eq!(pressed, vec![Gesture::LongPress(vec2(10, 10))]);
+        let end = g.touch(
+            0,
+            TouchPhase::Ended,
+            vec2(10, 10),
+            t + Duration::from_millis(700),
+        );
+        assert!(end.is_empty());
+    }
+    #[test]
+    fn test_swipe() {
+        let mut g = GestureRecognizer::new();
+        let t = Instant::now();
+        g.touch(0, TouchPhase::Started, vec2(0, 0), t);
+        g.touch(
+            0,
+            TouchPhase::Moved,
+            vec2(100, 0),
+            t + Duration::from_millis(100),
+        );
+        let end = g.touch(
+            0,
+            TouchPhase::Ended,
+            vec2(200, 0),
+            t + Duration::from_millis(200),
+        );
+        match end.as_slice() {
+            [Gesture::Swipe {
+                start,
+                end,
+                velocity,
+            }] => {
+                assert_eq!(*start, vec2(0, 0));
+                assert_eq!(*end, vec2(200, 0));
+                assert!((velocity.x - 1000.0).abs() < 1.0);
+            }
+            other => panic!("Expected swipe, got {:?}", other),
+        }
+    }
+    #[test]
+    fn test_pinch_and_rotate() {
+        let mut g = GestureRecognizer::new();
+        let t = Instant::now();
+        g.touch(0, TouchPhase::Started, vec2(0, 0), t);
+        g.touch(1, TouchPhase::Started, vec2(100, 0), t);
+        let pinch = g.touch(1, TouchPhase::Moved, vec2(200, 0), t);
+        assert_eq!(
+            pinch,
+            vec![Gesture::Pinch {
+                center: vec2(100, 0),
+                scale: 2.0
+            }]
+        );
+        let rotate = g.touch(1, TouchPhase::Moved, vec2(0, 200), t);
+        match rotate.as_slice() {
+            [Gesture::Rotate { angle, .. }] => {
+                assert!((angle - std::f32::consts::FRAC_PI_2).abs() < 0.001)
+            }
+            other => panic!("Expected rotate, got {:?}", other),
+        }
+        let end = g.touch(0, TouchPhase::Ended, vec2(0, 0), t);
+        assert!(end.is_empty());
+    }
+}
@@ -0,0 +1,327 @@
+use crate::events::{Key, KeyState, MouseButton};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+}
+impl From<Key> for Input {
+    fn from(key: Key) -> Self {
+        Input::Key(key)
+    }
+}
+impl From<MouseButton> for Input {
+    fn from(button: MouseButton) -> Self {
+        Input::Mouse(button)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct InputRecord {
+    pub input: Input,
+    pub state: KeyState,
+    pub frame: u64,
+    pub time: Instant,
+    /// Everything held down right after this input, including itself when pressed.
+    pub held: Vec<Input>,
+}
+
+#[derive(Clone, Debug)]
+pub enum ComboStep {
+    Press(Input),
+    /// One of these is pressed while the rest are already held, like a diagonal on a stick.
+    Chord(Vec<Input>),
+    /// All of these are pressed within InputBuffer::simultaneous_frames of each other.
+    Simultaneous(Vec<Input>),
+}
+
+/// A declarative input sequence, built step by step.
+/// ```ignore
+/// Combo::new("hadouken", 30)
+///     .press(Key::Down)
+///     .chord([Key::Down, Key::Right])
+///     .press(Key::Right)
+///     .press(Key::J);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Combo {
+    pub name: String,
+    pub steps: Vec<ComboStep>,
+    /// The whole sequence must fit in this many frames.
+    pub max_frames: u64,
+    /// Largest gap allowed between two steps, in frames.
+    pub leniency: u64,
+}
+impl Combo {
+    pub fn new(name: &str, max_frames: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            steps: vec![],
+            max_frames,
+            leniency: max_frames,
+        }
+    }
+    pub fn leniency(mut self, frames: u64) -> Self {
+        self.leniency = frames;
+        self
+    }
+    pub fn press(mut self, input: impl Into<Input>) -> Self {
+        self.steps.push(ComboStep::Press(input.into()));
+        self
+    }
+    pub fn chord<I: Into<Input>>(mut self, inputs: impl IntoIterator<Item = I>) -> Self {
+        self.steps.push(ComboStep::Chord(
+            inputs.into_iter().map(|i| i.into()).collect(),
+        ));
+        self
+    }
+    pub fn simultaneous<I: Into<Input>>(mut self, inputs: impl IntoIterator<Item = I>) -> Self {
+        self.steps.push(ComboStep::Simultaneous(
+            inputs.into_iter().map(|i| i.into()).collect(),
+        ));
+        self
+    }
+}
+
+/// Time-stamped history of key and mouse presses, checked against registered combos on every press.
+#[derive(Clone, Debug)]
+pub struct InputBuffer {
+    pub capacity: usize,
+    pub simultaneous_frames: u64,
+    history: VecDeque<InputRecord>,
+    held: Vec<Input>,
+    combos: Vec<Combo>,
+    frame: u64,
+}
+impl Default for InputBuffer {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            simultaneous_frames: 3,
+            history: VecDeque::new(),
+            held: vec![],
+            combos: vec![],
+            frame: 0,
+        }
+    }
+}
+impl InputBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add_combo(&mut self, combo: Combo) {
+        self.combos.push(combo);
+    }
+    pub fn remove_combo(&mut self, name: &str) {
+        self.combos.retain(|c| c.name != name);
+    }
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+    /// Forget held inputs, their releases are missed while the window is unfocused.
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+    pub fn history(&self) -> impl Iterator<Item = &InputRecord> {
+        self.history.iter()
+    }
+    /// Record an input, returns the names of any combos it completed.
+    pub fn record(&mut self, input: Input, state: KeyState, time: Instant) -> Vec<String> {
+        match state {
+            KeyState::Pressed => {
+                if self.held.contains(&input) {
+                    return vec![]; // key repeat
+                }
+                self.held.push(input);
+            }
+            KeyState::Released => self.held.retain(|i| *i != input),
+        }
+        self.history.push_back(InputRecord {
+            input,
+            state,
+            frame: self.frame,
+            time,
+            held: self.held.clone(),
+        });
+        while self.history.len() > self.capacity {
+            self.history.pop_front();
+        }
+        if state == KeyState::Released {
+            return vec![];
+        }
+        self.combos
+            .iter()
+            .filter(|c| self.matches(c))
+            .map(|c| c.name.clone())
+            .collect()
+    }
+    pub fn matches(&self, combo: &Combo) -> bool {
+        let newest = match self.history.back() {
+            Some(r) if r.state == KeyState::Pressed => r.frame,
+            _ => return false,
+        };
+        let (last, rest) = match combo.steps.split_last() {
+            Some(steps) => steps,
+            None => return false,
+        };
+        match self.step_at(last, self.history.len() - 1) {
+            Some((start, frame)) => self
+                .match_steps(combo, rest, start, frame)
+                .is_some_and(|first| newest - first <= combo.max_frames),
+            None => false,
+        }
+    }
+    /// Match steps backwards from cursor, trying every candidate so greedy choices can't hide a match.
+    /// Returns the frame of the first step.
+    fn match_steps(
+        &self,
+        combo: &Combo,
+        steps: &[ComboStep],
+        cursor: usize,
+        next_frame: u64,
+    ) -> Option<u64> {
+        let (step, rest) = match steps.split_last() {
+            Some(steps) => steps,
+            None => return Some(next_frame),
+        };
+        for i in (0..cursor).rev() {
+            if next_frame - self.history[i].frame > combo.leniency {
+                break;
+            }
+            if let Some((start, frame)) = self.step_at(step, i) {
+                if let Some(first) = self.match_steps(combo, rest, start, frame) {
+                    return Some(first);
+                }
+            }
+        }
+        None
+    }
+    /// Does the record at index satisfy step? Returns the earliest record index and frame it used.
+    fn step_at(&self, step: &ComboStep, index: usize) -> Option<(usize, u64)> {
+        let record = &self.history[index];
+        if record.state != KeyState::Pressed {
+            return None;
+        }
+        match step {
+            ComboStep::Press(input) => (record.input == *input).then_some((index, record.frame)),
+            ComboStep::Chord(inputs) => (inputs.contains(&record.input)
+                && inputs.iter().all(|i| record.held.contains(i)))
+            .then_some((index, record.frame)),
+            ComboStep::Simultaneous(inputs) => {
+                if !inputs.contains(&record.input) {
+                    return None;
+                }
+                let mut start = (index, record.frame);
+                for input in inputs.iter().filter(|i| **i != record.input) {
+                    let other = (0..index).rev().find(|j| {
+                        let r = &self.history[*j];
+                        r.state == KeyState::Pressed
+                            && r.input == *input
+                            && record.frame - r.frame <= self.simultaneous_frames
+                    })?;
+                    if other < start.0 {
+                        start = (other, self.history[other].frame);
+                    }
+                }
+                Some(start)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(b: &mut InputBuffer, key: Key) -> Vec<String> {
+        b.record(key.into(), KeyState::Pressed, Instant::now())
+    }
+    fn release(b: &mut InputBuffer, key: Key) {
+        b.record(key.into(), KeyState::Released, Instant::now());
+    }
+    fn fireball() -> Combo {
+        Combo::new("fireball", 20)
+            .press(Key::Down)
+            .chord([Key::Down, Key::Right])
+            .press(Key::Right)
+            .press(Key::J)
+    }
+
+    #[test]
+    fn test_combo_completes() {
+        let mut b = InputBuffer::new();
+        b.add_combo(fireball());
+        assert!(press(&mut b, Key::Down).is_empty());
+        b.next_frame();
+        assert!(press(&mut b, Key::Right).is_empty());
+        b.next_frame();
+        release(&mut b, Key::Down);
+        release(&mut b, Key::Right);
+        b.next_frame();
+        press(&mut b, Key::Right);
+        b.next_frame();
+        assert_eq!(press(&mut b, Key::J), vec!["fireball".to_string()]);
+    }
+    #[test]
+    fn test_combo_too_slow() {
+        let mut b = InputBuffer::new();
+        b.add_combo(fireball());
+        press(&mut b, Key::Down);
+        press(&mut b, Key::Right);
+        release(&mut b, Key::Down);
+        release(&mut b, Key::Right);
+        (0..30).for_each(|_| b.next_frame());
+        press(&mut b, Key::Right);
+        assert!(press(&mut b, Key::J).is_empty());
+    }
+    #[test]
+    fn test_simultaneous_window() {
+        let mut b = InputBuffer::new();
+        b.add_combo(Combo::new("throw", 10).simultaneous([Key::J, Key::K]));
+        press(&mut b, Key::J);
+        (0..5).for_each(|_| b.next_frame());
+        assert!(press(&mut b, Key::K).is_empty());
+        release(&mut b, Key::J);
+        release(&mut b, Key::K);
+        press(&mut b, Key::K);
+        b.next_frame();
+        assert_eq!(press(&mut b, Key::J), vec!["throw".to_string()]);
+    }
+    #[test]
+    fn test_leniency_between_steps() {
+        let mut b = InputBuffer::new();
+        b.add_combo(
+            Combo::new("double", 30)
+                .leniency(4)
+                .press(Key::A)
+                .press(Key::A),
+        );
+        press(&mut b, Key::A);
+        release(&mut b, Key::A);
+        (0..6).for_each(|_| b.next_frame());
+        assert!(press(&mut b, Key::A).is_empty());
+        release(&mut b, Key::A);
+        b.next_frame();
+        assert_eq!(press(&mut b, Key::A), vec!["double".to_string()]);
+    }
+    #[test]
+    fn test_release_all() {
+        let mut b = InputBuffer::new();
+        b.add_combo(Combo::new("double", 30).press(Key::A).press(Key::A));
+        press(&mut b, Key::A);
+        b.release_all();
+        assert_eq!(press(&mut b, Key::A), vec!["double".to_string()]);
+        assert!(b
+            .history()
+            .last()
+            .is_some_and(|r| r.held == [Input::Key(Key::A)]));
+    }
+}
@@ -2,24 +2,33 @@
 
 pub mod core;
 pub mod events;
+pub mod gestures;
+pub mod input_buffer;
 pub mod main_loop;
 pub mod math;
 pub mod mesh;
+pub mod post_process;
 pub mod shape_pipeline;
+pub mod tiled;
+pub mod tilemap;
 pub mod util;
 
 pub mod prelude {
     pub use {
-        crate::core::{Image, NGCore, NGError},
+        crate::core::{Image, ImageOptions, Material, NGCore, NGError},
         crate::events::{Event, Key, KeyState},
         crate::math::{angle_vec2, vec2, Vec2},
-        crate::mesh::{FillStyleShorthand, Font, Mesh, MeshBuilder},
-        crate::shape_pipeline::ShapeGfx,
-        crate::util::{create_texture_atlas, Ani, Camera, Color},
+        crate::mesh::{FillStyleShorthand, Font, Insets, Mesh, MeshBuilder, NineSlice, SliceFill},
+        crate::post_process::PostEffect,
+        crate::shape_pipeline::{BlendMode, MaskMode, ShapeGfx, SpriteParams, Tag},
+        crate::tiled::{load_tiled, TiledMap},
+        crate::tilemap::Tilemap,
+        crate::util::{create_texture_atlas, Ani, Camera, Color, StreamingImage},
         crate::{start, GransealGameConfig, NeoGransealEventHandler},
     };
 }
 use crate::main_loop::main_loop;
+use log::warn;
 use prelude::*;
 use wgpu::util::DeviceExt;
 use winit::event_loop::EventLoopBuilder;
@@ -35,6 +44,8 @@ pub struct GransealGameConfig {
     pub msaa: MSAA,
     pub fullscreen: bool,
     pub pixel_mode: bool, //TODO have this align all drawing to the pixel.
+    pub unfocused: BackgroundPolicy,
+    pub hidden: BackgroundPolicy,
 }
 impl Default for GransealGameConfig {
     fn default() -> Self {
@@ -48,6 +59,8 @@ impl Default for GransealGameConfig {
             msaa: MSAA::Enable4x,
             fullscreen: false,
             pixel_mode: false,
+            unfocused: BackgroundPolicy::Run,
+            hidden: BackgroundPolicy::Throttle(2),
         }
     }
 }
@@ -83,6 +96,26 @@ impl GransealGameConfig {
         self.fullscreen = v;
         self
     }
+    /// What to do while the window doesn't have focus.
+    pub fn unfocused(mut self, policy: BackgroundPolicy) -> Self {
+        self.unfocused = policy;
+        self
+    }
+    /// What to do while the window is minimized, occluded or otherwise not visible.
+    pub fn hidden(mut self, policy: BackgroundPolicy) -> Self {
+        self.hidden = policy;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum BackgroundPolicy {
+    /// Keep updating and drawing at full speed.
+    Run,
+    /// Keep updating and drawing, limited to this many frames per second.
+    Throttle(u32),
+    /// Stop sending Update, Draw is only sent when the window needs repainting.
+    Pause,
 }
 #[derive(Clone, Debug, Copy)]
 pub enum MSAA {
@@ -120,6 +153,20 @@ pub trait NeoGransealEventHandler {
 pub trait NGRenderPipeline {
     fn render(&mut self, core: &mut NGCore) -> Result<(), NGError>;
     fn render_image(&mut self, core: &mut NGCore, texture: crate::core::Image, replace: bool);
+    /// Draw into an image made with NGCore::create_render_target, using its own attachments.
+    /// The default only calls render_image, so it doesn't clear or use the target's depth and
+    /// MSAA attachments.
+    fn render_target(
+        &mut self,
+        core: &mut NGCore,
+        target: crate::core::Image,
+        clear: Option<Color>,
+    ) {
+        if clear.is_some() {
+            warn!("This pipeline doesn't support render targets, the clear is ignored.");
+        }
+        self.render_image(core, target, false);
+    }
     fn set_data(&mut self, core: &mut NGCore, data: Box<dyn std::any::Any>);
     fn set_globals(&mut self, globals: GlobalUniforms);
     fn resized(&mut self, core: &mut NGCore, width: u32, height: u32);
@@ -128,6 +175,34 @@ pub trait NGRenderPipeline {
 pub struct GlobalUniforms {
     bind_group_layout: wgpu::BindGroupLayout,
     bind_group: wgpu::BindGroup,
+    view_buffer: wgpu::Buffer,
+}
+
+/// The camera as the shaders see it, see Camera::view.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck_derive::Pod, bytemuck_derive::Zeroable)]
+pub(crate) struct ViewUniform {
+    pub(crate) offset: [f32; 2],
+    pub(crate) origin: [f32; 2],
+    pub(crate) zoom: f32,
+    pub(crate) rotation: f32,
+    _pad: [f32; 2],
+}
+impl ViewUniform {
+    pub(crate) fn new(offset: Vec2, origin: Vec2, zoom: f32, rotation: f32) -> Self {
+        Self {
+            offset: [offset.x, offset.y],
+            origin: [origin.x, origin.y],
+            zoom,
+            rotation,
+            _pad: [0.0; 2],
+        }
+    }
+}
+impl Default for ViewUniform {
+    fn default() -> Self {
+        Self::new(Vec2::ZERO, Vec2::ZERO, 1.0, 0.0)
+    }
 }
 impl GlobalUniforms {
     fn new(core: &NGCore, screen: (f32, f32)) -> Self {
@@ -147,6 +222,13 @@ impl GlobalUniforms {
                     ),
                     usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
                 });
+        let view_buffer = core
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("View Uniform Buffer"),
+                contents: bytemuck::bytes_of(&ViewUniform::default()),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
 
         let bind_group_layout =
             core.device
@@ -173,6 +255,16 @@ impl GlobalUniforms {
                             },
                             count: None,
                         },
+                        wgpu::BindGroupLayoutEntry {
+                            binding: 2,
+                            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                            ty: wgpu::BindingType::Buffer {
+                                ty: wgpu::BufferBindingType::Uniform,
+                                has_dynamic_offset: false,
+                                min_binding_size: None,
+                            },
+                            count: None,
+                        },
                     ],
                 });
         let bind_group = core.device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -187,11 +279,16 @@ impl GlobalUniforms {
                     binding: 1,
                     resource: time_uniform_buffer.as_entire_binding(),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: view_buffer.as_entire_binding(),
+                },
             ],
         });
         Self {
             bind_group_layout,
             bind_group,
+            view_buffer,
         }
     }
 }
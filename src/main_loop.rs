@@ -1,14 +1,18 @@
-use crate::events::{map_events, map_keys};
+use crate::events::{
+    map_device_events, map_events, map_file_events, map_key_state, map_keys, map_mouse_buttons,
+    map_touch_phase, TouchPhase,
+};
+use crate::input_buffer::Input;
+use crate::math::{vec2, Vec2};
 use crate::shape_pipeline::SimpleShapeRenderPipeline;
 use crate::{
     core::{NGCommand, NGCore},
-    events, GlobalUniforms,
+    events, BackgroundPolicy, GlobalUniforms,
 };
 use log::{error, warn};
-use std::thread::sleep;
 use std::time::Duration;
-use winit::event::{ElementState, KeyEvent, MouseButton};
-use winit::window::Fullscreen;
+use winit::event::{DeviceEvent, ElementState, KeyEvent, MouseButton};
+use winit::window::{CursorGrabMode, Fullscreen};
 use winit::{
     event::{Event, WindowEvent},
     event_loop,
@@ -21,6 +25,9 @@ pub(crate) fn main_loop(
 ) {
     env_logger::init();
     let mut delta = std::time::Instant::now();
+    let mut last_frame = std::time::Instant::now();
+    let mut redraw_pending = false;
+    let mut paused = false;
     let mut frames = 0;
     let one_second = Duration::from_secs(1);
     let mut frame_timer = std::time::Instant::now();
@@ -32,10 +39,10 @@ pub(crate) fn main_loop(
     h.event(&mut core, events::Event::Load);
 
     let _ = e_loop.run(move |event, window| {
-        window.set_control_flow(event_loop::ControlFlow::Poll);
+        // Commands run in the order they were issued, so render targets are drawn before the frames using them.
         while !core.cmd_queue.is_empty() {
-            match core.cmd_queue.pop() {
-                Some(command) => match command {
+            for command in std::mem::take(&mut core.cmd_queue) {
+                match command {
                     NGCommand::AddPipeline(p) => {
                         pipelines.push(p);
                     }
@@ -51,6 +58,18 @@ pub(crate) fn main_loop(
                             error!("Tried to render to invalid pipeline at index {:?}", index);
                         }
                     }
+                    NGCommand::RenderTarget(index, data, img, clear) => {
+                        if let Some(renderer) = pipelines.get_mut(index) {
+                            renderer.set_globals(GlobalUniforms::new(
+                                &core,
+                                (img.size().x, img.size().y),
+                            ));
+                            renderer.set_data(&mut core, data);
+                            renderer.render_target(&mut core, img, clear);
+                        } else {
+                            error!("Tried to render to invalid pipeline at index {:?}", index);
+                        }
+                    }
                     NGCommand::Render(index, data) => {
                         let size = {
                             let d = core.window.inner_size();
@@ -74,6 +93,18 @@ pub(crate) fn main_loop(
                         frames += 1;
                     }
                     NGCommand::SetCursorVisibility(v) => core.window.set_cursor_visible(v),
+                    NGCommand::SetCursorGrab(grab) => {
+                        let result = if grab {
+                            core.window
+                                .set_cursor_grab(CursorGrabMode::Locked)
+                                .or_else(|_| core.window.set_cursor_grab(CursorGrabMode::Confined))
+                        } else {
+                            core.window.set_cursor_grab(CursorGrabMode::None)
+                        };
+                        if let Err(err) = result {
+                            warn!("Couldn't change cursor grab: {:?}", err);
+                        }
+                    }
                     NGCommand::SetTitle(title) => {
                         core.config.title = title;
                         core.window.set_title(core.config.title.as_str());
@@ -81,15 +112,34 @@ pub(crate) fn main_loop(
                     NGCommand::CustomEvent(event) => {
                         h.event(&mut core, events::Event::Custom(event));
                     }
-                },
-                None => {}
-            };
+                }
+            }
         }
         match event {
             Event::WindowEvent { event, window_id } if window_id == core.window.id() => {
                 if let Some(nge) = map_events(&event) {
                     h.event(&mut core, nge);
                 }
+                if let Some(nge) = map_file_events(&event, core.state.mouse.pos) {
+                    h.event(&mut core, nge);
+                }
+                let input = match &event {
+                    WindowEvent::KeyboardInput { event, .. } => {
+                        Some((Input::Key(map_keys(&event.physical_key)), &event.state))
+                    }
+                    WindowEvent::MouseInput { button, state, .. } => {
+                        Some((Input::Mouse(map_mouse_buttons(button)), state))
+                    }
+                    _ => None,
+                };
+                if let Some((input, state)) = input {
+                    let combos =
+                        core.inputs
+                            .record(input, map_key_state(state), std::time::Instant::now());
+                    for combo in combos {
+                        h.event(&mut core, events::Event::Combo(combo));
+                    }
+                }
                 match event {
                     WindowEvent::Resized(size) => {
                         if size.width > 0 && size.height > 0 {
@@ -107,16 +157,19 @@ pub(crate) fn main_loop(
                         }
                     }
                     WindowEvent::RedrawRequested => {
+                        redraw_pending = false;
                         let elapsed = delta.elapsed();
                         delta = std::time::Instant::now();
-                        h.event(&mut core, events::Event::Update(elapsed));
-                        h.event(&mut core, events::Event::Draw);
-                        if let Some(visible) = core.window.is_visible() {
-                            if !visible {
-                                sleep(std::time::Duration::from_secs_f32(0.5))
-                            }
+                        last_frame = delta;
+                        core.inputs.next_frame();
+                        for gesture in core.gestures.update(delta) {
+                            h.event(&mut core, events::Event::Gesture(gesture));
+                        }
+                        if background_policy(&core) != BackgroundPolicy::Pause {
+                            h.event(&mut core, events::Event::Update(elapsed));
+                            core.state.mouse.motion = Vec2::ZERO;
                         }
-                        core.window.request_redraw();
+                        h.event(&mut core, events::Event::Draw);
                     }
                     WindowEvent::CloseRequested => {
                         window.exit();
@@ -158,6 +211,36 @@ pub(crate) fn main_loop(
                         core.state.mouse.pos.x = position.x as f32;
                         core.state.mouse.pos.y = position.y as f32;
                     }
+                    WindowEvent::Touch(touch) => {
+                        let pos = vec2(touch.location.x, touch.location.y);
+                        let phase = map_touch_phase(&touch.phase);
+                        match phase {
+                            TouchPhase::Started | TouchPhase::Moved => {
+                                core.state.touches.insert(touch.id, pos);
+                            }
+                            TouchPhase::Ended | TouchPhase::Cancelled => {
+                                core.state.touches.remove(&touch.id);
+                            }
+                        }
+                        let gestures =
+                            core.gestures
+                                .touch(touch.id, phase, pos, std::time::Instant::now());
+                        for gesture in gestures {
+                            h.event(&mut core, events::Event::Gesture(gesture));
+                        }
+                    }
+                    WindowEvent::CursorEntered { .. } => core.state.mouse.inside = true,
+                    WindowEvent::CursorLeft { .. } => core.state.mouse.inside = false,
+                    WindowEvent::Focused(focused) => {
+                        core.state.focused = focused;
+                        if !focused {
+                            core.inputs.release_all();
+                        }
+                    }
+                    WindowEvent::Occluded(occluded) => core.state.occluded = occluded,
+                    WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                        core.state.scale_factor = scale_factor;
+                    }
                     WindowEvent::MouseInput { button, state, .. } => match button {
                         MouseButton::Left => {
                             core.state.mouse.left = match state {
@@ -184,7 +267,66 @@ pub(crate) fn main_loop(
                     _ => {}
                 }
             }
+            Event::DeviceEvent { event, .. } => {
+                if let DeviceEvent::MouseMotion { delta } = event {
+                    core.state.mouse.motion += vec2(delta.0, delta.1);
+                }
+                if let Some(nge) = map_device_events(&event) {
+                    h.event(&mut core, nge);
+                }
+            }
+            Event::AboutToWait => {
+                let now = std::time::Instant::now();
+                let policy = background_policy(&core);
+                if paused && policy != BackgroundPolicy::Pause {
+                    // Don't hand the time spent paused to the next Update.
+                    paused = false;
+                    delta = now;
+                }
+                match policy {
+                    BackgroundPolicy::Run => {
+                        if !redraw_pending {
+                            redraw_pending = true;
+                            core.window.request_redraw();
+                        }
+                        window.set_control_flow(event_loop::ControlFlow::Poll);
+                    }
+                    BackgroundPolicy::Throttle(fps) => {
+                        let next_frame =
+                            last_frame + Duration::from_secs_f32(1.0 / fps.max(1) as f32);
+                        if now >= next_frame && !redraw_pending {
+                            redraw_pending = true;
+                            core.window.request_redraw();
+                        }
+                        window.set_control_flow(event_loop::ControlFlow::WaitUntil(next_frame));
+                    }
+                    BackgroundPolicy::Pause => {
+                        if !paused {
+                            // One more Draw, so a pause menu can be shown.
+                            paused = true;
+                            if !redraw_pending {
+                                redraw_pending = true;
+                                core.window.request_redraw();
+                            }
+                        }
+                        window.set_control_flow(event_loop::ControlFlow::Wait);
+                    }
+                }
+            }
             _ => (),
         }
     });
 }
+
+fn background_policy(core: &NGCore) -> BackgroundPolicy {
+    let hidden = core.state.occluded
+        || core.window.is_visible() == Some(false)
+        || core.window.is_minimized() == Some(true);
+    if hidden {
+        core.config.hidden
+    } else if !core.state.focused {
+        core.config.unfocused
+    } else {
+        BackgroundPolicy::Run
+    }
+}
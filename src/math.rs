@@ -247,6 +247,36 @@ impl Matrix3x3 {
             row3: [0.0, 0.0, 1.0],
         }
     }
+    /// 2D affine translation, for use with transform_point.
+    pub fn translation(t: Vec2) -> Self {
+        Self {
+            row1: [1.0, 0.0, t.x],
+            row2: [0.0, 1.0, t.y],
+            row3: [0.0, 0.0, 1.0],
+        }
+    }
+    pub fn scale(s: Vec2) -> Self {
+        Self {
+            row1: [s.x, 0.0, 0.0],
+            row2: [0.0, s.y, 0.0],
+            row3: [0.0, 0.0, 1.0],
+        }
+    }
+    /// Skew angles in radians, x leans along the x axis as y grows.
+    pub fn skew(angles: Vec2) -> Self {
+        Self {
+            row1: [1.0, angles.x.tan(), 0.0],
+            row2: [angles.y.tan(), 1.0, 0.0],
+            row3: [0.0, 0.0, 1.0],
+        }
+    }
+    /// Treats the matrix as a 2D affine transform.
+    pub fn transform_point(&self, p: Vec2) -> Vec2 {
+        Vec2::new(
+            self.row1[0] * p.x + self.row1[1] * p.y + self.row1[2],
+            self.row2[0] * p.x + self.row2[1] * p.y + self.row2[2],
+        )
+    }
     /// Column's indexes are 1,2,3 to follow standard math notation.
     pub fn column(&self, index: usize) -> Vec3 {
         Vec3 {
@@ -1917,4 +1947,12 @@ mod tests {
             }
         );
     }
+    #[test]
+    fn test_affine_transform_point() {
+        let m = Matrix3x3::translation(Vec2::new(10, 20)) * Matrix3x3::scale(Vec2::new(2, 3));
+        assert_eq!(m.transform_point(Vec2::new(1, 1)), Vec2::new(12, 23));
+        let skew = Matrix3x3::skew(Vec2::new(std::f32::consts::FRAC_PI_4, 0.0));
+        let p = skew.transform_point(Vec2::new(0, 2));
+        assert!((p.x - 2.0).abs() < 0.0001 && (p.y - 2.0).abs() < 0.0001);
+    }
 }
@@ -25,12 +25,135 @@ pub enum LineStyle {
     Left,
     Right,
 }
+/// How the corners between segments of a stroke are drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineJoin {
+    /// Sharp corners, beveled when the point would reach further than limit times the width.
+    Miter(f32),
+    Round,
+    Bevel,
+}
+/// How the ends of an open stroke, and of every dash, are drawn.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LineCap {
+    Butt,
+    /// Extended by half the width.
+    Square,
+    Round,
+}
+/// Alternating dash and gap lengths, starting offset into the pattern. An odd number of
+/// lengths is repeated, like in SVG.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Dashes {
+    lengths: [f32; Dashes::MAX],
+    count: usize,
+    pub offset: f32,
+}
+impl Dashes {
+    pub const MAX: usize = 8;
+    /// Lengths past the first MAX are ignored.
+    pub fn new(pattern: &[f32], offset: f32) -> Self {
+        let count = pattern.len().min(Self::MAX);
+        let mut lengths = [0.0; Self::MAX];
+        lengths[..count].copy_from_slice(&pattern[..count]);
+        Self {
+            lengths,
+            count,
+            offset,
+        }
+    }
+    pub fn pattern(&self) -> &[f32] {
+        &self.lengths[..self.count]
+    }
+}
+/// Joins, caps, dashes and tapering of MeshBuilder::stroke_path and polyline.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub join: LineJoin,
+    pub cap: LineCap,
+    pub dashes: Option<Dashes>,
+    /// Width at the start and end of each stroked line as a multiple of the thickness.
+    pub taper: (f32, f32),
+}
+impl Default for Stroke {
+    fn default() -> Self {
+        Self {
+            join: LineJoin::Miter(4.0),
+            cap: LineCap::Butt,
+            dashes: None,
+            taper: (1.0, 1.0),
+        }
+    }
+}
+/// The borders of a nine-slice image, left, top, right and bottom.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Insets {
+    /// In pixels of the image.
+    Pixels(f32, f32, f32, f32),
+    /// In percent of the image's width or height.
+    Percent(f32, f32, f32, f32),
+}
+impl Insets {
+    /// Resolved to pixels for an image of the given size.
+    pub fn pixels(&self, size: Vec2) -> (f32, f32, f32, f32) {
+        match *self {
+            Insets::Pixels(l, t, r, b) => (l, t, r, b),
+            Insets::Percent(l, t, r, b) => (
+                l / 100.0 * size.x,
+                t / 100.0 * size.y,
+                r / 100.0 * size.x,
+                b / 100.0 * size.y,
+            ),
+        }
+    }
+}
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum SliceFill {
+    #[default]
+    Stretch,
+    /// Repeats the part at its size, parts under a pixel or needing over MAX_TILES stretch instead.
+    Tile,
+}
+impl SliceFill {
+    pub const MAX_TILES: f32 = 1024.0;
+}
+/// How an image is cut into nine parts, the corners keep their size while edges and centre fill the rest.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NineSlice {
+    pub insets: Insets,
+    pub edges: SliceFill,
+    pub center: SliceFill,
+}
+impl NineSlice {
+    pub fn new(insets: Insets) -> Self {
+        Self {
+            insets,
+            edges: SliceFill::Stretch,
+            center: SliceFill::Stretch,
+        }
+    }
+    pub fn pixels(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Self::new(Insets::Pixels(left, top, right, bottom))
+    }
+    pub fn percent(left: f32, top: f32, right: f32, bottom: f32) -> Self {
+        Self::new(Insets::Percent(left, top, right, bottom))
+    }
+    pub fn edges(mut self, fill: SliceFill) -> Self {
+        self.edges = fill;
+        self
+    }
+    pub fn center(mut self, fill: SliceFill) -> Self {
+        self.center = fill;
+        self
+    }
+}
 #[derive(Copy, Clone, Debug)]
 pub struct MBState {
     pub cursor: Vec2,
     pub filled: bool,
     pub fill_style: FillStyle,
     pub line_style: LineStyle,
+    pub stroke: Stroke,
     pub thickness: f32,
     pub rotation: f32,
     pub rot_origin: Vec2,
@@ -46,6 +169,7 @@ impl Default for MBState {
             filled: true,
             fill_style: Solid(Color::WHITE),
             line_style: LineStyle::Center,
+            stroke: Stroke::default(),
             thickness: 1.0,
             rotation: 0.0,
             rot_origin: Vec2::new(0, 0),
@@ -118,6 +242,19 @@ impl MeshBuilder {
     pub fn set_line_style(&mut self, style: LineStyle) {
         self.state.line_style = style;
     }
+    pub fn set_stroke(&mut self, stroke: Stroke) {
+        self.state.stroke = stroke;
+    }
+    pub fn set_line_join(&mut self, join: LineJoin) {
+        self.state.stroke.join = join;
+    }
+    pub fn set_line_cap(&mut self, cap: LineCap) {
+        self.state.stroke.cap = cap;
+    }
+    /// Dash and gap lengths of following strokes, an empty pattern draws solid lines again.
+    pub fn set_dashes(&mut self, pattern: &[f32], offset: f32) {
+        self.state.stroke.dashes = (!pattern.is_empty()).then(|| Dashes::new(pattern, offset));
+    }
     pub fn set_resolution(&mut self, res: f32) {
         self.state.resolution = res;
     }
@@ -149,23 +286,51 @@ impl MeshBuilder {
         mesh.translate(self.state.cursor);
         self.meshes.push(mesh);
     }
+    /// Stroke every line of path, curves included, with joins between their segments.
     pub fn stroke_path(&mut self, path: &PathData) {
-        for seg in path.segments.iter() {
-            for con in seg.contours.iter() {
-                match con {
-                    Contour::MoveTo(begin) => {
-                        self.move_to(*begin);
-                    }
-                    Contour::LineTo(end) => self.line_to(*end),
-                    Contour::QuadTo(cp, end) => self.quad_to(*cp, *end),
-                    Contour::CubicTo(cp1, cp2, end) => self.cubic_to(*cp1, *cp2, *end),
-                    Contour::ClosePath(bool) => {
-                        self.close_path(*bool);
-                    }
-                }
+        for (points, closed) in path_polylines(path, self.state.cursor, self.state.resolution) {
+            self.polyline(&points, closed);
+            if let (Some(first), Some(last)) = (points.first(), points.last()) {
+                self.state.path_start = *first;
+                self.set_cursor(if closed { *first } else { *last });
             }
         }
     }
+    /// Stroke points as one line with the current thickness, line style and stroke.
+    /// Like line_to the points aren't moved by the cursor.
+    pub fn polyline(&mut self, points: &[Vec2], closed: bool) {
+        let (start, end) = self.state.stroke.taper;
+        let lengths = points
+            .iter()
+            .scan(
+                (0.0, None),
+                |(length, last): &mut (f32, Option<Vec2>), p| {
+                    *length += last.map_or(0.0, |last| (*p - last).magnitude());
+                    *last = Some(*p);
+                    Some(*length)
+                },
+            )
+            .collect::<Vec<_>>();
+        let total = lengths.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+        let widths = lengths
+            .iter()
+            .map(|l| self.state.thickness * (start + (end - start) * l / total))
+            .collect::<Vec<_>>();
+        let mut m = stroke_polyline(
+            points,
+            &widths,
+            closed,
+            self.state.line_style,
+            &self.state.stroke,
+            self.state.fill_style,
+            self.state.resolution,
+        );
+        m.set_z_depth(self.state.z_depth);
+        self.do_rotation(&mut m);
+        m.image = self.state.image;
+        m.uv_project();
+        self.meshes.push(m);
+    }
     pub fn draw_polygon(&mut self, polygon: &Polygon) {
         for (start, end) in polygon.edges.iter() {
             self.line(polygon.points[*start], polygon.points[*end]);
@@ -202,6 +367,14 @@ impl MeshBuilder {
             m.translate(-offset);
         }
     }
+    /// A nine-slice of image at the cursor, see NineSlice.
+    pub fn nine_slice(&mut self, image: &Image, slice: NineSlice, size: Vec2) {
+        let mut m = nine_slice(image, slice, self.state.cursor, self.state.cursor + size);
+        m.style(self.state.fill_style);
+        m.set_z_depth(self.state.z_depth);
+        self.do_rotation(&mut m);
+        self.meshes.push(m);
+    }
     pub fn rounded_rect(&mut self, size: Vec2, radius: f32) {
         let mut m = if self.state.filled {
             rounded_rect_filled(
@@ -427,37 +600,11 @@ impl MeshBuilder {
     pub fn clear_meshes(&mut self) {
         self.meshes.clear();
     }
+    /// Combine everything drawn so far into one mesh. Parts using different images are kept
+    /// apart and drawn with their own texture.
     pub fn build(&mut self) -> Mesh {
-        let imgs = self
-            .meshes
-            .iter()
-            .filter_map(|m| m.image)
-            .collect::<Vec<_>>();
-        let ids = imgs
-            .iter()
-            .map(|img| {
-                if let Some((atlas_id, _)) = img.atlas {
-                    atlas_id
-                } else {
-                    img.texture
-                }
-            })
-            .collect::<Vec<_>>();
-        if !ids.is_empty() {
-            let first = *ids.first().unwrap();
-            for i in ids {
-                if i != first {
-                    panic!(
-                        "Meshes can't use more than one texture. Use an atlas, or only one image."
-                    )
-                }
-            }
-        }
-
         let mut m = combine(&mut self.meshes);
-        if !imgs.is_empty() {
-            m.image = imgs.first().copied();
-        } else {
+        if m.image.is_none() && m.parts.is_empty() {
             m.image = self.state.image;
         }
         m
@@ -472,6 +619,8 @@ pub struct Mesh {
     pub(crate) buffer: std::cell::Cell<bool>,
     pub(crate) dirty: std::cell::Cell<bool>,
     pub(crate) image: Option<Image>,
+    /// The first index of each run of triangles using one image, empty when the whole mesh uses image.
+    pub(crate) parts: Vec<(u32, Option<Image>)>,
 }
 impl FillStyleShorthand for Mesh {
     fn solid(&mut self, c: Color) {
@@ -498,6 +647,7 @@ impl Mesh {
     }
     pub fn texture(&mut self, image: &Image, uv_project: bool) {
         self.image = Some(image.to_owned());
+        self.parts.clear();
         if uv_project {
             self.uv_project();
         };
@@ -511,12 +661,66 @@ impl Mesh {
     }
     pub fn add(mut self, other: &Self) -> Self {
         let start = self.vertices.len() as u32;
+        let first_index = self.indices.len() as u32;
+        let mut parts = self.part_starts();
+        parts.extend(
+            other
+                .part_starts()
+                .into_iter()
+                .map(|(index, image)| (index + first_index, image)),
+        );
         self.vertices.extend(&other.vertices);
         let indices: Vec<u32> = other.indices.iter().map(|i| i + start).collect();
         self.indices.extend(indices);
+        self.set_parts(parts);
         self.dirty = true.into();
         self
     }
+    fn part_starts(&self) -> Vec<(u32, Option<Image>)> {
+        if self.parts.is_empty() {
+            vec![(0, self.image)]
+        } else {
+            self.parts.clone()
+        }
+    }
+    /// Merges neighbouring runs sampling the same texture, and drops empty ones.
+    fn set_parts(&mut self, parts: Vec<(u32, Option<Image>)>) {
+        let len = self.indices.len() as u32;
+        let mut merged: Vec<(u32, Option<Image>)> = vec![];
+        for (i, (start, image)) in parts.iter().enumerate() {
+            let end = parts.get(i + 1).map_or(len, |(next, _)| *next);
+            if end <= *start {
+                continue;
+            }
+            match merged.last() {
+                Some((_, last))
+                    if last.and_then(|l| l.texture_id()) == image.and_then(|i| i.texture_id()) => {}
+                _ => merged.push((*start, *image)),
+            }
+        }
+        self.image = merged.iter().find_map(|(_, image)| *image);
+        if merged.len() > 1 {
+            merged[0].0 = 0;
+            self.parts = merged;
+        } else {
+            self.parts.clear();
+        }
+    }
+    /// The index ranges of the mesh and the image each one is drawn with.
+    pub(crate) fn texture_parts(&self) -> Vec<(std::ops::Range<u32>, Option<Image>)> {
+        let len = self.indices.len() as u32;
+        if self.parts.is_empty() {
+            return vec![(0..len, self.image)];
+        }
+        self.parts
+            .iter()
+            .enumerate()
+            .map(|(i, (start, image))| {
+                let end = self.parts.get(i + 1).map_or(len, |(next, _)| *next);
+                (*start..end, *image)
+            })
+            .collect()
+    }
     pub fn min_x(&self) -> f32 {
         self.vertices
             .iter()
@@ -660,7 +864,7 @@ impl Mesh {
         self
     }
 }
-#[derive(Default)]
+#[derive(Clone, Debug, Default)]
 pub struct Polygon {
     pub points: Vec<Vec2>,
     pub edges: Vec<(usize, usize)>,
@@ -949,8 +1153,86 @@ pub fn rect_filled(top_left: Vec2, bottom_right: Vec2, style: FillStyle) -> Mesh
         buffer: false.into(),
         dirty: false.into(),
         image: None,
+        parts: vec![],
+    }
+}
+/// Pieces of one axis of a nine-slice, as (destination, source) ranges in pixels.
+fn slice_axis(src: (f32, f32), dst: (f32, f32), fill: SliceFill) -> Vec<((f32, f32), (f32, f32))> {
+    let (src_len, dst_len) = (src.1 - src.0, dst.1 - dst.0);
+    if dst_len <= 0.0 || src_len <= 0.0 {
+        return vec![];
+    }
+    match fill {
+        SliceFill::Tile if src_len >= 1.0 && dst_len / src_len <= SliceFill::MAX_TILES => {
+            let mut pieces = vec![];
+            let mut start = dst.0;
+            while start < dst.1 {
+                let len = src_len.min(dst.1 - start);
+                pieces.push(((start, start + len), (src.0, src.0 + len)));
+                start += len;
+            }
+            pieces
+        }
+        _ => vec![(dst, src)],
     }
 }
+/// A nine-slice of image filling the rectangle from top_left to bottom_right. The corners keep
+/// their size in pixels, unless the rectangle is too small for them.
+pub fn nine_slice(image: &Image, slice: NineSlice, top_left: Vec2, bottom_right: Vec2) -> Mesh {
+    let size = image.size();
+    let (l, t, r, b) = slice.insets.pixels(size);
+    let dst = bottom_right - top_left;
+    let shrink = |a: f32, b: f32, len: f32| {
+        if a + b > len && a + b > 0.0 {
+            len / (a + b)
+        } else {
+            1.0
+        }
+    };
+    let (sx, sy) = (shrink(l, r, dst.x), shrink(t, b, dst.y));
+    let columns = [
+        ((0.0, l), (0.0, l * sx)),
+        ((l, size.x - r), (l * sx, dst.x - r * sx)),
+        ((size.x - r, size.x), (dst.x - r * sx, dst.x)),
+    ];
+    let rows = [
+        ((0.0, t), (0.0, t * sy)),
+        ((t, size.y - b), (t * sy, dst.y - b * sy)),
+        ((size.y - b, size.y), (dst.y - b * sy, dst.y)),
+    ];
+    let (min, max) = image.get_uv();
+    let uv = |p: Vec2| min + (max - min) * vec2(p.x / size.x, p.y / size.y);
+    let mut mesh = Mesh::default();
+    for (j, (src_y, dst_y)) in rows.into_iter().enumerate() {
+        for (i, (src_x, dst_x)) in columns.into_iter().enumerate() {
+            let (fill_x, fill_y) = match (i, j) {
+                (1, 1) => (slice.center, slice.center),
+                (1, _) => (slice.edges, SliceFill::Stretch),
+                (_, 1) => (SliceFill::Stretch, slice.edges),
+                _ => (SliceFill::Stretch, SliceFill::Stretch),
+            };
+            for (dy, sy) in slice_axis(src_y, dst_y, fill_y) {
+                for (dx, sx) in slice_axis(src_x, dst_x, fill_x) {
+                    let start = mesh.vertices.len() as u32;
+                    let corners = [
+                        (vec2(dx.0, dy.0), vec2(sx.0, sy.0)),
+                        (vec2(dx.1, dy.0), vec2(sx.1, sy.0)),
+                        (vec2(dx.1, dy.1), vec2(sx.1, sy.1)),
+                        (vec2(dx.0, dy.1), vec2(sx.0, sy.1)),
+                    ];
+                    mesh.vertices.extend(corners.map(|(pos, src)| {
+                        let uv = uv(src);
+                        Vertex::point(top_left + pos).uv(uv.x, uv.y)
+                    }));
+                    mesh.indices
+                        .extend([2, 1, 0, 3, 2, 0].map(|index| index + start));
+                }
+            }
+        }
+    }
+    mesh.image = Some(*image);
+    mesh
+}
 pub fn rect_outlined(top_left: Vec2, bottom_right: Vec2, thickness: f32, style: FillStyle) -> Mesh {
     let (c1, c2, c3, c4) = style_colors(style);
     let mut mesh = Mesh::default();
@@ -1034,6 +1316,7 @@ pub fn oval_filled(
         buffer: false.into(),
         dirty: false.into(),
         image: None,
+        parts: vec![],
     }
 }
 pub fn oval_outlined(
@@ -1159,6 +1442,411 @@ pub fn line(begin: Vec2, end: Vec2, thickness: f32, style: LineStyle, fill: Fill
     }
     m
 }
+/// The lines of a path with curves flattened, and whether each one is closed. Segments that don't
+/// open with a MoveTo continue from where the previous one ended, the first from start.
+pub fn path_polylines(path: &PathData, start: Vec2, resolution: f32) -> Vec<(Vec<Vec2>, bool)> {
+    let mut lines = vec![];
+    let (mut cursor, mut path_start) = (start, start);
+    for segment in path.segments.iter() {
+        let mut points = vec![cursor];
+        for contour in segment.contours.iter() {
+            let last = points.last().copied().unwrap_or(cursor);
+            let steps = |end: Vec2| ((end - last).magnitude() / resolution).ceil().max(1.0) as i32;
+            match *contour {
+                Contour::MoveTo(start) => {
+                    if points.len() > 1 {
+                        lines.push((std::mem::take(&mut points), false));
+                    }
+                    points = vec![start];
+                    path_start = start;
+                }
+                Contour::LineTo(end) => points.push(end),
+                Contour::QuadTo(cp, end) => {
+                    let count = steps(end);
+                    points.extend(
+                        (1..=count)
+                            .map(|i| quadratic_to_point(i as f32 / count as f32, last, cp, end)),
+                    );
+                }
+                Contour::CubicTo(cp1, cp2, end) => {
+                    let count = steps(end);
+                    points.extend(
+                        (1..=count)
+                            .map(|i| cubic_to_point(i as f32 / count as f32, last, cp1, cp2, end)),
+                    );
+                }
+                Contour::ClosePath(closed) => {
+                    if points.len() > 1 {
+                        lines.push((std::mem::take(&mut points), closed));
+                    }
+                    points = vec![if closed { path_start } else { last }];
+                }
+            }
+        }
+        cursor = points.last().copied().unwrap_or(cursor);
+        if points.len() > 1 {
+            lines.push((points, false));
+        }
+    }
+    lines
+}
+
+/// Triangles of a stroke, wound so none get culled whichever way the line turns.
+#[derive(Default)]
+struct StrokeMesh {
+    mesh: Mesh,
+}
+impl StrokeMesh {
+    fn point(&mut self, p: Vec2) -> u32 {
+        self.mesh.vertices.push(Vertex::point(p));
+        self.mesh.vertices.len() as u32 - 1
+    }
+    fn pos(&self, i: u32) -> Vec2 {
+        let v = self.mesh.vertices[i as usize];
+        vec2(v.x, v.y)
+    }
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let area = (self.pos(b) - self.pos(a)).cross(&(self.pos(c) - self.pos(a)));
+        if area.abs() <= f32::EPSILON {
+            return;
+        }
+        let indices = if area > 0.0 { [a, c, b] } else { [a, b, c] };
+        self.mesh.indices.extend(indices);
+    }
+    /// Triangles from pivot to each edge of the outline.
+    fn fan(&mut self, pivot: u32, outline: &[u32]) {
+        for pair in outline.windows(2) {
+            self.triangle(pivot, pair[0], pair[1]);
+        }
+    }
+    /// Points on the arc around center from angle begin, sweep radians further.
+    fn arc(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        begin: f32,
+        sweep: f32,
+        resolution: f32,
+    ) -> Vec<u32> {
+        let steps = (sweep.abs() / (PI / 8.0))
+            .max(sweep.abs() * radius / resolution)
+            .ceil()
+            .max(1.0) as u32;
+        (0..=steps)
+            .map(|i| {
+                let a = begin + sweep * i as f32 / steps as f32;
+                self.point(center + math::angle_vec2(a) * radius)
+            })
+            .collect()
+    }
+}
+
+fn left_normal(d: Vec2) -> Vec2 {
+    vec2(d.y, -d.x)
+}
+
+/// Where the segments before and after a point end and start, as left and right corners.
+struct JoinCorners {
+    left_in: u32,
+    right_in: u32,
+    left_out: u32,
+    right_out: u32,
+}
+
+/// The join at p between the segment coming in along d_a and the one leaving along d_b.
+/// Segments meet at the inner corner, the gap on the outside is filled from there so nothing
+/// is drawn twice. When the inner corner would fall past a short segment they overlap instead.
+fn stroke_join(
+    out: &mut StrokeMesh,
+    p: Vec2,
+    (left, right): (f32, f32),
+    (d_a, len_a): (Vec2, f32),
+    (d_b, len_b): (Vec2, f32),
+    join: LineJoin,
+    resolution: f32,
+) -> JoinCorners {
+    let (n_a, n_b) = (left_normal(d_a), left_normal(d_b));
+    let turn = d_a.cross(&d_b);
+    if turn.abs() < 1e-4 && d_a.dot(&d_b) > 0.0 {
+        let (l, r) = (out.point(p + n_a * left), out.point(p - n_a * right));
+        return JoinCorners {
+            left_in: l,
+            right_in: r,
+            left_out: l,
+            right_out: r,
+        };
+    }
+    let outer_left = turn > 0.0;
+    let (outer, inner, side) = if outer_left {
+        (left, right, 1.0)
+    } else {
+        (right, left, -1.0)
+    };
+    let (o_a, o_b) = (n_a * side, n_b * side);
+    let bisector = o_a + o_b;
+    let miter = (bisector.magnitude() > 1e-4).then(|| {
+        let m = bisector.normalize();
+        (m, m.dot(&o_a))
+    });
+    let corner = miter
+        .map(|(m, cos)| p - m * (inner / cos))
+        .filter(|c| (*c - p).dot(&d_a).abs() <= len_a && (*c - p).dot(&d_b).abs() <= len_b);
+    let outer_in = out.point(p + o_a * outer);
+    let outer_out = out.point(p + o_b * outer);
+    let (pivot, inner_in, inner_out) = match corner {
+        Some(corner) => {
+            let c = out.point(corner);
+            (c, c, c)
+        }
+        None => (
+            out.point(p),
+            out.point(p - o_a * inner),
+            out.point(p - o_b * inner),
+        ),
+    };
+    let mut outline = vec![outer_in];
+    match (join, miter) {
+        (LineJoin::Miter(limit), Some((m, cos))) if 1.0 / cos <= limit => {
+            outline.push(out.point(p + m * (outer / cos)));
+        }
+        (LineJoin::Round, _) => {
+            let sweep = o_a.cross(&o_b).atan2(o_a.dot(&o_b));
+            outline.extend(out.arc(p, outer, o_a.angle2(), sweep, resolution));
+        }
+        _ => {}
+    }
+    outline.push(outer_out);
+    out.fan(pivot, &outline);
+    if outer_left {
+        JoinCorners {
+            left_in: outer_in,
+            right_in: inner_in,
+            left_out: outer_out,
+            right_out: inner_out,
+        }
+    } else {
+        JoinCorners {
+            left_in: inner_in,
+            right_in: outer_in,
+            left_out: inner_out,
+            right_out: outer_out,
+        }
+    }
+}
+
+/// The end of a stroke at p, d pointing away from the line. Returns the left and right corners
+/// as seen walking out of the line.
+fn stroke_cap(
+    out: &mut StrokeMesh,
+    p: Vec2,
+    d: Vec2,
+    (left, right): (f32, f32),
+    cap: LineCap,
+    resolution: f32,
+) -> (u32, u32) {
+    let n = left_normal(d);
+    let half = (left + right) / 2.0;
+    match cap {
+        LineCap::Butt => (out.point(p + n * left), out.point(p - n * right)),
+        LineCap::Square => {
+            let p = p + d * half;
+            (out.point(p + n * left), out.point(p - n * right))
+        }
+        LineCap::Round => {
+            let center = p + n * ((left - right) / 2.0);
+            let arc = out.arc(center, half, n.angle2(), PI, resolution);
+            let c = out.point(center);
+            out.fan(c, &arc);
+            (arc[0], arc[arc.len() - 1])
+        }
+    }
+}
+
+fn stroke_line(
+    out: &mut StrokeMesh,
+    points: &[(Vec2, f32)],
+    closed: bool,
+    sides: (f32, f32),
+    stroke: &Stroke,
+    resolution: f32,
+) {
+    let extents = |w: f32| (w * sides.0, w * sides.1);
+    let closed = closed && points.len() > 2;
+    let count = points.len();
+    if count == 1 {
+        let (p, w) = points[0];
+        match stroke.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                let corners = [vec2(-1, -1), vec2(1, -1), vec2(1, 1), vec2(-1, 1)]
+                    .map(|c| out.point(p + c * (w / 2.0)));
+                out.fan(corners[0], &corners[1..]);
+            }
+            LineCap::Round => {
+                let arc = out.arc(p, w / 2.0, 0.0, TAU, resolution);
+                let c = out.point(p);
+                out.fan(c, &arc);
+            }
+        }
+        return;
+    }
+    let segments = if closed { count } else { count - 1 };
+    let segment = |i: usize| {
+        let d = points[(i + 1) % count].0 - points[i].0;
+        (d.normalize(), d.magnitude())
+    };
+    let corners = (0..count)
+        .map(|i| {
+            let (p, w) = points[i];
+            match (closed, i) {
+                (false, 0) => {
+                    let (d, _) = segment(0);
+                    let (r, l) = stroke_cap(
+                        out,
+                        p,
+                        -d,
+                        (sides.1 * w, sides.0 * w),
+                        stroke.cap,
+                        resolution,
+                    );
+                    JoinCorners {
+                        left_in: l,
+                        right_in: r,
+                        left_out: l,
+                        right_out: r,
+                    }
+                }
+                (false, i) if i == count - 1 => {
+                    let (d, _) = segment(i - 1);
+                    let (l, r) = stroke_cap(out, p, d, extents(w), stroke.cap, resolution);
+                    JoinCorners {
+                        left_in: l,
+                        right_in: r,
+                        left_out: l,
+                        right_out: r,
+                    }
+                }
+                _ => stroke_join(
+                    out,
+                    p,
+                    extents(w),
+                    segment((i + count - 1) % count),
+                    segment(i),
+                    stroke.join,
+                    resolution,
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+    for i in 0..segments {
+        let (a, b) = (&corners[i], &corners[(i + 1) % count]);
+        let (a_left, a_right, b_left, b_right) = (a.left_out, a.right_out, b.left_in, b.right_in);
+        out.triangle(a_left, a_right, b_right);
+        out.triangle(a_left, b_right, b_left);
+    }
+}
+
+/// Pieces of a line covered by the dashes, with widths interpolated where they're cut.
+fn dash_line(points: &[(Vec2, f32)], dashes: &Dashes) -> Vec<Vec<(Vec2, f32)>> {
+    let mut pattern = dashes.pattern().to_vec();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..);
+    }
+    let total: f32 = pattern.iter().sum();
+    if points.is_empty() || total <= 0.0 || pattern.iter().any(|l| *l < 0.0) {
+        return vec![points.to_vec()];
+    }
+    let mut index = 0;
+    let mut phase = dashes.offset.rem_euclid(total);
+    while phase >= pattern[index] && index < pattern.len() - 1 {
+        phase -= pattern[index];
+        index += 1;
+    }
+    let mut remaining = (pattern[index] - phase).max(0.0);
+    let mut pieces = vec![];
+    let mut current = if index % 2 == 0 {
+        vec![points[0]]
+    } else {
+        vec![]
+    };
+    for pair in points.windows(2) {
+        let ((a, wa), (b, wb)) = (pair[0], pair[1]);
+        let length = (b - a).magnitude();
+        let mut travelled = 0.0;
+        while length - travelled > remaining {
+            travelled += remaining;
+            let t = travelled / length;
+            let cut = (a + (b - a) * t, wa + (wb - wa) * t);
+            if index % 2 == 0 {
+                current.push(cut);
+                pieces.push(std::mem::take(&mut current));
+            } else {
+                current = vec![cut];
+            }
+            index = (index + 1) % pattern.len();
+            remaining = pattern[index];
+        }
+        remaining -= length - travelled;
+        if index % 2 == 0 {
+            current.push((b, wb));
+        }
+    }
+    if !current.is_empty() {
+        pieces.push(current);
+    }
+    pieces
+}
+
+/// A stroke along points with the width at each point, a missing width repeats the last one.
+/// line_style puts the stroke centered on the points or to their left or right, seen walking
+/// along them. Joins, caps and dashes come from stroke.
+pub fn stroke_polyline(
+    points: &[Vec2],
+    widths: &[f32],
+    closed: bool,
+    line_style: LineStyle,
+    stroke: &Stroke,
+    fill: FillStyle,
+    resolution: f32,
+) -> Mesh {
+    let sides = match line_style {
+        LineStyle::Center => (0.5, 0.5),
+        LineStyle::Left => (1.0, 0.0),
+        LineStyle::Right => (0.0, 1.0),
+    };
+    let mut line: Vec<(Vec2, f32)> = vec![];
+    for (i, p) in points.iter().enumerate() {
+        let width = widths.get(i).or(widths.last()).copied().unwrap_or(1.0);
+        if line
+            .last()
+            .is_none_or(|(last, _)| (*p - *last).magnitude() > 1e-4)
+        {
+            line.push((*p, width));
+        }
+    }
+    if closed && line.len() > 2 && (line[0].0 - line[line.len() - 1].0).magnitude() <= 1e-4 {
+        line.pop();
+    }
+    let mut out = StrokeMesh::default();
+    match stroke.dashes {
+        Some(dashes) if !line.is_empty() => {
+            if closed {
+                line.push(line[0]);
+            }
+            for piece in dash_line(&line, &dashes) {
+                let mut piece = piece;
+                piece.dedup_by(|a, b| (a.0 - b.0).magnitude() <= 1e-4);
+                stroke_line(&mut out, &piece, false, sides, stroke, resolution);
+            }
+        }
+        _ if line.is_empty() => {}
+        _ => stroke_line(&mut out, &line, closed, sides, stroke, resolution),
+    }
+    let mut m = out.mesh;
+    m.uv_project();
+    m.style(fill);
+    m
+}
 pub fn raw_triangle_filled(p1: Vec2, p2: Vec2, p3: Vec2, c1: Color, c2: Color, c3: Color) -> Mesh {
     let mut m = Mesh::default();
     m.vertices.push(Vertex::new(p1.x, p1.y).rgba(c1));
@@ -1403,6 +2091,7 @@ pub fn load_meshes2(data: &str, scale: f32) -> HashMap<&str, Mesh> {
                 buffer: false.into(),
                 dirty: false.into(),
                 image: None,
+                parts: vec![],
             },
         );
     }
@@ -1474,3 +2163,143 @@ impl Font {
         mesh
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nine_slice() {
+        let image = Image::default().sub_image(Vec2::ZERO, vec2(30, 30));
+        let slice = NineSlice::percent(10.0, 10.0, 10.0, 10.0).center(SliceFill::Tile);
+        let mesh = nine_slice(&image, slice, Vec2::ZERO, vec2(60, 33));
+        // 8 stretched border pieces and a centre of 3 by 2 tiles.
+        assert_eq!(mesh.vertices.len(), (8 + 6) * 4);
+        assert_eq!(mesh.max_x(), 60.0);
+        assert_eq!(mesh.max_y(), 33.0);
+        let squashed = nine_slice(
+            &image,
+            NineSlice::pixels(10.0, 10.0, 10.0, 10.0),
+            Vec2::ZERO,
+            vec2(10, 40),
+        );
+        assert!(squashed.vertices.iter().all(|v| v.x <= 10.0));
+        assert_eq!(
+            slice_axis((0.0, 0.001), (0.0, 1e6), SliceFill::Tile).len(),
+            1
+        );
+        assert_eq!(slice_axis((0.0, 2.0), (0.0, 1e6), SliceFill::Tile).len(), 1);
+        assert_eq!(slice_axis((0.0, 2.0), (0.0, 5.0), SliceFill::Tile).len(), 3);
+    }
+    #[test]
+    fn test_texture_parts() {
+        let mut a = Image::default();
+        a.texture = 1;
+        let mut b = Image::default();
+        b.texture = 2;
+        let mut mb = MeshBuilder::default();
+        mb.set_image(&a);
+        mb.rect(Vec2::new(10, 10));
+        mb.rect(Vec2::new(10, 10));
+        mb.set_image(&b);
+        mb.rect(Vec2::new(10, 10));
+        mb.clear_image();
+        mb.rect(Vec2::new(10, 10));
+        let mesh = mb.build();
+        let parts = mesh
+            .texture_parts()
+            .into_iter()
+            .map(|(range, image)| (range, image.map(|i| i.texture)))
+            .collect::<Vec<_>>();
+        assert_eq!(parts, [(0..12, Some(1)), (12..18, Some(2)), (18..24, None)]);
+        assert_eq!(mesh.image.map(|i| i.texture), Some(1));
+    }
+    /// Summed triangle area, which only matches the shape's area without gaps or overlaps.
+    /// Also checks every triangle faces the way the pipeline doesn't cull.
+    fn stroke_area(points: &[Vec2], closed: bool, stroke: Stroke) -> f32 {
+        let mesh = stroke_polyline(
+            points,
+            &[2.0],
+            closed,
+            LineStyle::Center,
+            &stroke,
+            Solid(Color::WHITE),
+            8.0,
+        );
+        mesh.indices
+            .chunks(3)
+            .map(|t| {
+                let p = [t[0], t[1], t[2]]
+                    .map(|i| vec2(mesh.vertices[i as usize].x, mesh.vertices[i as usize].y));
+                let area = (p[1] - p[0]).cross(&(p[2] - p[0])) / 2.0;
+                assert!(area < 0.0);
+                -area
+            })
+            .sum()
+    }
+    #[test]
+    fn test_stroke_joins_and_caps() {
+        let corner = [vec2(0, 0), vec2(10, 0), vec2(10, 10)];
+        let miter = Stroke::default();
+        assert!((stroke_area(&corner, false, miter) - 40.0).abs() < 1e-3);
+        let bevel = Stroke {
+            join: LineJoin::Bevel,
+            ..miter
+        };
+        assert!((stroke_area(&corner, false, bevel) - 39.5).abs() < 1e-3);
+        let limited = Stroke {
+            join: LineJoin::Miter(1.2),
+            ..miter
+        };
+        assert!((stroke_area(&corner, false, limited) - 39.5).abs() < 1e-3);
+        let square = Stroke {
+            cap: LineCap::Square,
+            ..miter
+        };
+        assert!((stroke_area(&corner, false, square) - 44.0).abs() < 1e-3);
+        let round = Stroke {
+            join: LineJoin::Round,
+            cap: LineCap::Round,
+            ..miter
+        };
+        // The quarter circle past the bevel and two half circle caps, a bit less as polygons.
+        let exact = 39.5 + (PI / 4.0 - 0.5) + PI;
+        let area = stroke_area(&corner, false, round);
+        assert!(area < exact && area > exact - 0.2);
+        let square_loop = [vec2(0, 0), vec2(10, 0), vec2(10, 10), vec2(0, 10)];
+        assert!((stroke_area(&square_loop, true, miter) - (12.0 * 12.0 - 8.0 * 8.0)).abs() < 1e-3);
+    }
+    #[test]
+    fn test_dash_line() {
+        let line = [(vec2(0, 0), 1.0), (vec2(10, 0), 3.0)];
+        let pieces = dash_line(&line, &Dashes::new(&[2.0, 3.0], 1.0));
+        let spans = pieces
+            .iter()
+            .map(|piece| (piece[0].0.x, piece[piece.len() - 1].0.x))
+            .collect::<Vec<_>>();
+        assert_eq!(spans, [(0.0, 1.0), (4.0, 6.0), (9.0, 10.0)]);
+        assert!((pieces[1][0].1 - 1.8).abs() < 1e-5);
+    }
+    #[test]
+    fn test_path_polylines_continue_segments() {
+        let path = crate::util::PathBuilder::default()
+            .move_to(vec2(0, 0))
+            .line_to(vec2(10, 0))
+            .line_to(vec2(10, 10))
+            .close_path(true)
+            .line_to(vec2(0, 10))
+            .close_path(false)
+            .quadratic_to(vec2(0, 20), vec2(10, 20))
+            .close_path(false)
+            .build();
+        let lines = path_polylines(&path, vec2(-5, -5), 1.0);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            (vec![vec2(0, 0), vec2(10, 0), vec2(10, 10)], true)
+        );
+        assert_eq!(lines[1], (vec![vec2(0, 0), vec2(0, 10)], false));
+        assert_eq!(lines[2].0[0], vec2(0, 10));
+        assert_eq!(lines[2].0.last(), Some(&vec2(10, 20)));
+    }
+}
@@ -0,0 +1,483 @@
+use crate::util::Color;
+use crate::NGCore;
+use bytemuck_derive::{Pod, Zeroable};
+use std::collections::HashMap;
+use wgpu::TextureViewDescriptor;
+
+/// Full-screen effects applied in order to the finished frame, set them with NGCore::post_effects.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PostEffect {
+    /// Gaussian blur, radius in pixels.
+    Blur { radius: f32 },
+    /// Bright parts above threshold (0.0 - 1.0 luminance) are blurred by radius and added back.
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        radius: f32,
+    },
+    /// Darkens the edges, radius and softness are in uv units from the center.
+    Vignette {
+        strength: f32,
+        radius: f32,
+        softness: f32,
+    },
+    /// Scanlines and screen curvature. scroll moves the scanlines over time.
+    Crt {
+        scanlines: f32,
+        curvature: f32,
+        lines: f32,
+        scroll: f32,
+    },
+    /// Splits red and blue apart towards the edges, offset in pixels.
+    ChromaticAberration { offset: f32 },
+    /// brightness is added, contrast and saturation are multipliers around 1.0, tint multiplies.
+    ColorGrade {
+        brightness: f32,
+        contrast: f32,
+        saturation: f32,
+        tint: Color,
+    },
+}
+impl PostEffect {
+    pub fn crt() -> Self {
+        PostEffect::Crt {
+            scanlines: 0.25,
+            curvature: 0.05,
+            lines: 480.0,
+            scroll: 0.01,
+        }
+    }
+    pub fn bloom() -> Self {
+        PostEffect::Bloom {
+            threshold: 0.7,
+            intensity: 1.0,
+            radius: 8.0,
+        }
+    }
+    pub fn vignette() -> Self {
+        PostEffect::Vignette {
+            strength: 0.8,
+            radius: 0.75,
+            softness: 0.45,
+        }
+    }
+    pub fn grade() -> Self {
+        PostEffect::ColorGrade {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            tint: Color::WHITE,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Pod, Zeroable)]
+struct PostParams {
+    a: [f32; 4],
+    b: [f32; 4],
+}
+
+/// One full-screen draw. Indices are into the intermediate textures, out None is the final output.
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct PostStep {
+    entry: &'static str,
+    src: usize,
+    base: usize,
+    out: Option<usize>,
+    params: PostParams,
+}
+
+const INTERMEDIATES: usize = 3;
+const ENTRIES: [&str; 8] = [
+    "fs_copy",
+    "fs_blur",
+    "fs_bright",
+    "fs_bloom",
+    "fs_vignette",
+    "fs_crt",
+    "fs_chromatic",
+    "fs_grade",
+];
+
+/// Expand effects into passes, the frame starts in texture 0 and the last pass writes the output.
+fn post_steps(effects: &[PostEffect]) -> Vec<PostStep> {
+    let mut steps = vec![];
+    let mut cur = 0;
+    let step = |entry, src, out, a: [f32; 4]| PostStep {
+        entry,
+        src,
+        base: src,
+        out: Some(out),
+        params: PostParams { a, b: [0.0; 4] },
+    };
+    for effect in effects {
+        let o1 = (cur + 1) % INTERMEDIATES;
+        let o2 = (cur + 2) % INTERMEDIATES;
+        match *effect {
+            PostEffect::Blur { radius } => {
+                let spacing = radius / 4.0;
+                steps.push(step("fs_blur", cur, o1, [spacing, 0.0, 0.0, 0.0]));
+                steps.push(step("fs_blur", o1, o2, [0.0, spacing, 0.0, 0.0]));
+                cur = o2;
+            }
+            PostEffect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => {
+                let spacing = radius / 4.0;
+                steps.push(step("fs_bright", cur, o1, [threshold, 0.0, 0.0, 0.0]));
+                steps.push(step("fs_blur", o1, o2, [spacing, 0.0, 0.0, 0.0]));
+                steps.push(step("fs_blur", o2, o1, [0.0, spacing, 0.0, 0.0]));
+                steps.push(PostStep {
+                    base: cur,
+                    ..step("fs_bloom", o1, o2, [intensity, 0.0, 0.0, 0.0])
+                });
+                cur = o2;
+            }
+            PostEffect::Vignette {
+                strength,
+                radius,
+                softness,
+            } => {
+                steps.push(step(
+                    "fs_vignette",
+                    cur,
+                    o1,
+                    [strength, radius, softness, 0.0],
+                ));
+                cur = o1;
+            }
+            PostEffect::Crt {
+                scanlines,
+                curvature,
+                lines,
+                scroll,
+            } => {
+                steps.push(step(
+                    "fs_crt",
+                    cur,
+                    o1,
+                    [scanlines, curvature, lines, scroll],
+                ));
+                cur = o1;
+            }
+            PostEffect::ChromaticAberration { offset } => {
+                steps.push(step("fs_chromatic", cur, o1, [offset, 0.0, 0.0, 0.0]));
+                cur = o1;
+            }
+            PostEffect::ColorGrade {
+                brightness,
+                contrast,
+                saturation,
+                tint,
+            } => {
+                steps.push(PostStep {
+                    params: PostParams {
+                        a: [brightness, contrast, saturation, 0.0],
+                        b: [tint.r, tint.g, tint.b, tint.a],
+                    },
+                    ..step("fs_grade", cur, o1, [0.0; 4])
+                });
+                cur = o1;
+            }
+        }
+    }
+    match steps.last_mut() {
+        Some(last) => last.out = None,
+        None => steps.push(PostStep {
+            out: None,
+            ..step("fs_copy", cur, cur, [0.0; 4])
+        }),
+    }
+    steps
+}
+
+pub(crate) struct PostProcessor {
+    layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipelines: HashMap<&'static str, wgpu::RenderPipeline>,
+    params_buffer: wgpu::Buffer,
+    params_stride: u64,
+    params_capacity: usize,
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    textures: Vec<wgpu::Texture>,
+}
+impl PostProcessor {
+    pub(crate) fn new(
+        core: &NGCore,
+        globals: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let layout = core
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Post Process BGL"),
+                entries: &[
+                    texture_entry(0),
+                    texture_entry(1),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: true,
+                            min_binding_size: wgpu::BufferSize::new(
+                                std::mem::size_of::<PostParams>() as u64,
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let sampler = core.device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Process Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let shader = core
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Post Process Shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("post_process.wgsl").into()),
+            });
+        let pipeline_layout = core
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Post Process Pipeline Layout"),
+                bind_group_layouts: &[globals, &layout],
+                push_constant_ranges: &[],
+            });
+        let pipelines = ENTRIES
+            .iter()
+            .map(|entry| {
+                let pipeline =
+                    core.device
+                        .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                            label: Some(entry),
+                            layout: Some(&pipeline_layout),
+                            vertex: wgpu::VertexState {
+                                module: &shader,
+                                entry_point: "vs_main",
+                                buffers: &[],
+                            },
+                            primitive: wgpu::PrimitiveState::default(),
+                            depth_stencil: None,
+                            multisample: wgpu::MultisampleState::default(),
+                            fragment: Some(wgpu::FragmentState {
+                                module: &shader,
+                                entry_point: entry,
+                                targets: &[Some(wgpu::ColorTargetState {
+                                    format,
+                                    blend: None,
+                                    write_mask: wgpu::ColorWrites::ALL,
+                                })],
+                            }),
+                            multiview: None,
+                        });
+                (*entry, pipeline)
+            })
+            .collect();
+        let params_stride = (std::mem::size_of::<PostParams>() as u64)
+            .max(core.device.limits().min_uniform_buffer_offset_alignment as u64);
+        let params_capacity = 16;
+        let params_buffer = Self::params_buffer(core, params_stride, params_capacity);
+        Self {
+            layout,
+            sampler,
+            pipelines,
+            params_buffer,
+            params_stride,
+            params_capacity,
+            format,
+            size: (0, 0),
+            textures: vec![],
+        }
+    }
+    fn params_buffer(core: &NGCore, stride: u64, capacity: usize) -> wgpu::Buffer {
+        core.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post Process Params Buffer"),
+            size: stride * capacity as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+    /// The texture the scene should be rendered into, sized to the surface.
+    pub(crate) fn scene(&mut self, core: &NGCore) -> &wgpu::Texture {
+        let size = (
+            core.surface_configuration.width.max(1),
+            core.surface_configuration.height.max(1),
+        );
+        if self.size != size || self.textures.is_empty() {
+            self.textures.iter().for_each(|t| t.destroy());
+            self.textures = (0..INTERMEDIATES)
+                .map(|_| {
+                    core.device.create_texture(&wgpu::TextureDescriptor {
+                        label: Some("Post Process Texture"),
+                        size: wgpu::Extent3d {
+                            width: size.0,
+                            height: size.1,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: self.format,
+                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                })
+                .collect();
+            self.size = size;
+        }
+        &self.textures[0]
+    }
+    /// Run the effect chain over the scene texture and write the result to output.
+    pub(crate) fn apply(
+        &mut self,
+        core: &NGCore,
+        globals: &wgpu::BindGroup,
+        effects: &[PostEffect],
+        output: &wgpu::Texture,
+    ) {
+        let steps = post_steps(effects);
+        if steps.len() > self.params_capacity {
+            self.params_capacity = steps.len().next_power_of_two();
+            self.params_buffer =
+                Self::params_buffer(core, self.params_stride, self.params_capacity);
+        }
+        for (i, step) in steps.iter().enumerate() {
+            core.queue.write_buffer(
+                &self.params_buffer,
+                i as u64 * self.params_stride,
+                bytemuck::bytes_of(&step.params),
+            );
+        }
+        let views = self
+            .textures
+            .iter()
+            .map(|t| t.create_view(&TextureViewDescriptor::default()))
+            .collect::<Vec<_>>();
+        let output_view = output.create_view(&TextureViewDescriptor::default());
+        let mut encoder = core
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Post Process Command Encoder"),
+            });
+        for (i, step) in steps.iter().enumerate() {
+            let bind_group = core.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post Process BG"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&views[step.src]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&views[step.base]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &self.params_buffer,
+                            offset: 0,
+                            size: wgpu::BufferSize::new(std::mem::size_of::<PostParams>() as u64),
+                        }),
+                    },
+                ],
+            });
+            let target = match step.out {
+                Some(out) => &views[out],
+                None => &output_view,
+            };
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Post Process Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&self.pipelines[step.entry]);
+            pass.set_bind_group(0, globals, &[]);
+            pass.set_bind_group(1, &bind_group, &[(i as u64 * self.params_stride) as u32]);
+            pass.draw(0..3, 0..1);
+        }
+        core.queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_steps_ping_pong() {
+        let steps = post_steps(&[PostEffect::bloom(), PostEffect::vignette()]);
+        let entries = steps.iter().map(|s| s.entry).collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            vec!["fs_bright", "fs_blur", "fs_blur", "fs_bloom", "fs_vignette"]
+        );
+        for step in &steps {
+            assert_ne!(step.out, Some(step.src));
+            assert_ne!(step.out, Some(step.base));
+        }
+        assert_eq!(steps[3].base, 0);
+        assert_eq!(steps[4].src, steps[3].out.unwrap());
+        assert_eq!(steps.last().unwrap().out, None);
+    }
+    #[test]
+    fn test_empty_chain_copies() {
+        let steps = post_steps(&[]);
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].entry, "fs_copy");
+        assert_eq!(steps[0].out, None);
+    }
+    #[test]
+    fn test_shader_validates() {
+        use wgpu::naga;
+        let module =
+            naga::front::wgsl::parse_str(include_str!("post_process.wgsl")).expect("Parse shader");
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .expect("Validate shader");
+    }
+}
@@ -0,0 +1,115 @@
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> screen: vec2<f32>;
+@group(0) @binding(1)
+var<uniform> timer: f32;
+
+struct PostParams {
+    a: vec4<f32>,
+    b: vec4<f32>,
+}
+// src is the previous pass, base is the input of the current effect (bloom needs both).
+@group(1) @binding(0)
+var src: texture_2d<f32>;
+@group(1) @binding(1)
+var base: texture_2d<f32>;
+@group(1) @binding(2)
+var samp: sampler;
+@group(1) @binding(3)
+var<uniform> params: PostParams;
+
+// One triangle covering the screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+fn luma(c: vec3<f32>) -> f32 {
+    return dot(c, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+@fragment
+fn fs_copy(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(src, samp, in.uv);
+}
+
+// a.xy: step between taps in pixels.
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    let texel = params.a.xy / vec2<f32>(textureDimensions(src));
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    var color = textureSample(src, samp, in.uv) * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = texel * f32(i);
+        color += textureSample(src, samp, in.uv + offset) * weights[i];
+        color += textureSample(src, samp, in.uv - offset) * weights[i];
+    }
+    return color;
+}
+
+// a.x: threshold.
+@fragment
+fn fs_bright(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(src, samp, in.uv);
+    let factor = smoothstep(params.a.x, params.a.x + 0.1, luma(color.rgb));
+    return vec4<f32>(color.rgb * factor, 1.0);
+}
+
+// a.x: intensity. src is the blurred highlights, base the original frame.
+@fragment
+fn fs_bloom(in: VertexOutput) -> @location(0) vec4<f32> {
+    let glow = textureSample(src, samp, in.uv).rgb * params.a.x;
+    let color = textureSample(base, samp, in.uv);
+    return vec4<f32>(color.rgb + glow, color.a);
+}
+
+// a.x: strength, a.y: radius, a.z: softness.
+@fragment
+fn fs_vignette(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(src, samp, in.uv);
+    let d = distance(in.uv, vec2<f32>(0.5, 0.5));
+    let v = smoothstep(params.a.y, params.a.y - params.a.z, d);
+    return vec4<f32>(color.rgb * mix(1.0, v, params.a.x), color.a);
+}
+
+// a.x: scanline strength, a.y: curvature, a.z: scanline count, a.w: scroll speed.
+@fragment
+fn fs_crt(in: VertexOutput) -> @location(0) vec4<f32> {
+    var uv = in.uv * 2.0 - 1.0;
+    uv *= 1.0 + params.a.y * dot(uv.yx, uv.yx);
+    uv = uv * 0.5 + 0.5;
+    let inside = step(0.0, uv.x) * step(uv.x, 1.0) * step(0.0, uv.y) * step(uv.y, 1.0);
+    let color = textureSample(src, samp, clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0)));
+    let line = sin((uv.y + timer * params.a.w) * params.a.z * 3.14159265) * 0.5 + 0.5;
+    let scan = mix(1.0, line, params.a.x);
+    return vec4<f32>(color.rgb * scan * inside, color.a);
+}
+
+// a.x: offset in pixels, pushed outwards from the center.
+@fragment
+fn fs_chromatic(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dir = (in.uv - 0.5) * 2.0;
+    let offset = dir * params.a.x / vec2<f32>(textureDimensions(src));
+    let r = textureSample(src, samp, in.uv + offset).r;
+    let g = textureSample(src, samp, in.uv).g;
+    let b = textureSample(src, samp, in.uv - offset);
+    return vec4<f32>(r, g, b.b, b.a);
+}
+
+// a.x: brightness, a.y: contrast, a.z: saturation, b: tint.
+@fragment
+fn fs_grade(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(src, samp, in.uv);
+    var rgb = color.rgb + params.a.x;
+    rgb = (rgb - 0.5) * params.a.y + 0.5;
+    rgb = mix(vec3<f32>(luma(rgb)), rgb, params.a.z);
+    rgb *= params.b.rgb;
+    return vec4<f32>(clamp(rgb, vec3<f32>(0.0), vec3<f32>(1.0)), color.a);
+}
@@ -0,0 +1,21 @@
+// Every loaded texture, indexed per object on devices with texture binding arrays.
+@group(3) @binding(0)
+var textures: binding_array<texture_2d<f32>>;
+@group(3) @binding(1)
+var samplers: binding_array<sampler>;
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    return in.color * textureSample(textures[in.texture], samplers[in.texture], in.tex) * in.tint;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+// Multiply and Screen blend with premultiplied color.
+@fragment
+fn fs_premultiplied(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
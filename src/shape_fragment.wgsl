@@ -0,0 +1,15 @@
+fn shade(in: VertexOutput) -> vec4<f32> {
+    return in.color * textureSample(tex,samp,in.tex) * in.tint;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+// Multiply and Screen blend with premultiplied color.
+@fragment
+fn fs_premultiplied(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(color.rgb * color.a, color.a);
+}
@@ -0,0 +1,9 @@
+// Writes which object covers each pixel for NGCore::pick, 0 where there's nothing.
+@fragment
+fn fs_pick(in: VertexOutput) -> @location(0) u32 {
+    let alpha = in.color.a * textureSample(tex, samp, in.tex).a * in.tint.a;
+    if alpha <= 0.0 {
+        discard;
+    }
+    return in.inst + 1u;
+}
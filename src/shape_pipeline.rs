@@ -1,11 +1,13 @@
-use crate::core::Image;
-use crate::math::Vec2;
+use crate::core::{Image, Material};
+use crate::math::{Matrix3x3, Vec2};
 use crate::mesh::*;
-use crate::{Color, GlobalUniforms, NGCore, NGError, NGRenderPipeline, MSAA};
+use crate::post_process::PostProcessor;
+use crate::util::Rectangle;
+use crate::{Camera, Color, GlobalUniforms, NGCore, NGError, NGRenderPipeline, ViewUniform, MSAA};
 use bytemuck_derive::{Pod, Zeroable};
 use log::{error, warn};
+use std::collections::{HashMap, HashSet};
 use std::default::Default;
-use wgpu::util::DeviceExt;
 use wgpu::{LoadOp, MultisampleState, StoreOp, TextureViewDescriptor};
 
 #[repr(C)]
@@ -76,6 +78,8 @@ pub struct SSRTransform {
     r: f32,
     rx: f32,
     ry: f32,
+    camera: u32,
+    m: [f32; 6],
 }
 impl SSRTransform {
     pub fn new(pos: Vec2, r: f32, origin: Vec2) -> Self {
@@ -85,8 +89,16 @@ impl SSRTransform {
             r,
             rx: origin.x,
             ry: origin.y,
+            camera: 0,
+            m: [1.0, 0.0, 0.0, 0.0, 1.0, 0.0],
         }
     }
+    pub fn with_matrix(mut self, m: Matrix3x3) -> Self {
+        self.m = [
+            m.row1[0], m.row1[1], m.row1[2], m.row2[0], m.row2[1], m.row2[2],
+        ];
+        self
+    }
 }
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
@@ -96,6 +108,123 @@ pub struct SSRMaterial {
     pub g: f32,
     pub b: f32,
     pub a: f32,
+    pub texture: u32,
+}
+/// User parameters handed to a custom material, read in WGSL as four vec4s via material_params(in).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+pub struct SSRMaterialParams(pub [f32; 16]);
+impl SSRMaterialParams {
+    /// Copies up to 16 values, the rest are zero.
+    pub fn new(values: &[f32]) -> Self {
+        let mut params = Self::default();
+        let len = values.len().min(16);
+        params.0[..len].copy_from_slice(&values[..len]);
+        params
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum BlendMode {
+    #[default]
+    Alpha,
+    Additive,
+    Multiply,
+    Screen,
+    PremultipliedAlpha,
+    Replace,
+}
+impl BlendMode {
+    /// Multiply and Screen need the shader to premultiply its color, their factors don't weigh
+    /// the source by its alpha.
+    pub(crate) fn premultiplied(&self) -> bool {
+        matches!(self, BlendMode::Multiply | BlendMode::Screen)
+    }
+    pub fn blend_state(&self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+        match self {
+            BlendMode::Alpha => BlendState::ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+            BlendMode::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            BlendMode::Screen => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::OneMinusSrc,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            },
+            BlendMode::PremultipliedAlpha => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Replace => BlendState::REPLACE,
+        }
+    }
+}
+
+/// Which side of the current mask later draws are kept on, see ShapeGfx::begin_mask.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub enum MaskMode {
+    #[default]
+    Inside,
+    Outside,
+}
+
+/// How an object uses the stencil buffer, compared against its stencil_ref.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub(crate) enum StencilMode {
+    #[default]
+    Off,
+    Test,
+    Increment,
+    Decrement,
+}
+impl StencilMode {
+    fn writes(&self) -> bool {
+        matches!(self, StencilMode::Increment | StencilMode::Decrement)
+    }
+    fn state(&self) -> wgpu::StencilState {
+        let (compare, pass_op) = match self {
+            StencilMode::Off => (wgpu::CompareFunction::Always, wgpu::StencilOperation::Keep),
+            StencilMode::Test => (wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep),
+            StencilMode::Increment => (
+                wgpu::CompareFunction::Equal,
+                wgpu::StencilOperation::IncrementClamp,
+            ),
+            StencilMode::Decrement => (
+                wgpu::CompareFunction::Equal,
+                wgpu::StencilOperation::DecrementClamp,
+            ),
+        };
+        let face = wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+        wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: !0,
+            write_mask: !0,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -105,8 +234,28 @@ pub struct SSRObjectInfo {
     pub(crate) start_index: u32,
     pub(crate) end_index: u32,
     pub(crate) start_vertice: u32,
+    pub(crate) end_vertice: u32,
+    /// The frontmost z of the object's vertices, used when sorting.
+    pub(crate) depth: f32,
+    /// Drawn after the opaque objects, back to front, and left out of the depth pre-pass.
+    pub(crate) translucent: bool,
+    pub(crate) blend: BlendMode,
+    pub(crate) material: Option<Material>,
+    pub(crate) stencil: StencilMode,
+    pub(crate) stencil_ref: u32,
+    pub(crate) clip: Option<Rectangle>,
+    /// Picks its texture from the texture binding array, so it doesn't break batches by texture.
+    pub(crate) bindless: bool,
 }
 pub type BufferedObjectID = usize;
+/// What NGCore::pick found under a pixel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Tag {
+    /// The object's index among everything drawn to the screen that frame.
+    Object(u32),
+    /// Set with ShapeGfx::set_tag or draw_mesh_tagged.
+    User(u32),
+}
 #[derive(Debug)]
 pub struct MeshBuffer {
     pub(crate) vertex_buffer: wgpu::Buffer,
@@ -117,22 +266,380 @@ pub struct MeshBuffer {
 pub struct SimpleShapeRenderPipeline {
     depth_stencil: wgpu::Texture,
     multisample: Option<wgpu::Texture>,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    trans_buffer: wgpu::Buffer,
-    mats_buffer: wgpu::Buffer,
+    vertex_buffer: GrowableBuffer,
+    index_buffer: GrowableBuffer,
+    trans_buffer: GrowableBuffer,
+    mats_buffer: GrowableBuffer,
+    params_buffer: GrowableBuffer,
+    data_bgl: wgpu::BindGroupLayout,
     data_bind_group: wgpu::BindGroup,
     globals: GlobalUniforms,
-    objects: Vec<SSRObjectInfo>,
-    pipeline: wgpu::RenderPipeline,
-    pipeline2: wgpu::RenderPipeline,
-    pipeline3: wgpu::RenderPipeline,
-    depth_pass: wgpu::RenderPipeline,
+    object_buffer: GrowableBuffer,
+    zero_buffer: GrowableBuffer,
+    /// Objects in draw order, with their index into the transform and material buffers.
+    objects: Vec<(u32, SSRObjectInfo)>,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    multisample_state: wgpu::MultisampleState,
+    surface_format: wgpu::TextureFormat,
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    post: Option<PostProcessor>,
+    bindless: Option<Bindless>,
+    /// User tags of this frame's objects by transform index.
+    tags: Vec<Option<u32>>,
+    pick_shader: wgpu::ShaderModule,
+    picker: Option<Picker>,
+}
+
+type MapResult = std::sync::mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>;
+
+/// The object ID target NGCore::pick renders into, and the readback of the pixel it asked for.
+struct Picker {
+    ids: wgpu::Texture,
+    depth: wgpu::Texture,
+    readback: wgpu::Buffer,
+    /// Tags of the frame being read back and the result of mapping the buffer.
+    pending: Option<(Vec<Option<u32>>, MapResult)>,
+}
+impl Picker {
+    const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+    fn new(core: &NGCore, size: (u32, u32)) -> Self {
+        let texture = |label, format| {
+            core.device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+                view_formats: &[],
+            })
+        };
+        Self {
+            ids: texture("Pick IDs", Self::FORMAT),
+            depth: texture(
+                "Pick Depth Stencil",
+                wgpu::TextureFormat::Depth32FloatStencil8,
+            ),
+            readback: core.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Pick Readback"),
+                size: std::mem::size_of::<u32>() as u64,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            pending: None,
+        }
+    }
+    /// Some once the pending readback finished, with what was under the pixel.
+    fn poll(&mut self, core: &NGCore) -> Option<Option<Tag>> {
+        let (_, receiver) = self.pending.as_ref()?;
+        core.device.poll(wgpu::Maintain::Poll);
+        let mapped = match receiver.try_recv() {
+            Ok(result) => result.is_ok(),
+            Err(std::sync::mpsc::TryRecvError::Empty) => return None,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => false,
+        };
+        let (tags, _) = self.pending.take()?;
+        if !mapped {
+            return Some(None);
+        }
+        let id = {
+            let data = self.readback.slice(..).get_mapped_range();
+            u32::from_ne_bytes([data[0], data[1], data[2], data[3]])
+        };
+        self.readback.unmap();
+        Some(pick_tag(id, &tags))
+    }
+}
+
+/// The ID pass writes the object index plus one, 0 is the cleared background.
+fn pick_tag(id: u32, tags: &[Option<u32>]) -> Option<Tag> {
+    let index = id.checked_sub(1)?;
+    Some(match tags.get(index as usize).copied().flatten() {
+        Some(tag) => Tag::User(tag),
+        None => Tag::Object(index),
+    })
+}
+
+/// The texture binding array variant of the default shader, used when the device supports it.
+struct Bindless {
+    layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    shader: wgpu::ShaderModule,
+    capacity: u32,
+    generation: Option<usize>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+impl Bindless {
+    fn new(core: &NGCore, layouts: &[&wgpu::BindGroupLayout], capacity: u32) -> Self {
+        let count = std::num::NonZeroU32::new(capacity);
+        let layout = core
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Texture Array BGL"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count,
+                    },
+                ],
+            });
+        let pipeline_layout = core
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("SSR Texture Array Pipeline Layout"),
+                bind_group_layouts: &[layouts, &[&layout]].concat(),
+                push_constant_ranges: &[],
+            });
+        let shader = core
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("SSR Texture Array Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("shape_shader.wgsl"),
+                        include_str!("shape_bindless.wgsl")
+                    )
+                    .into(),
+                ),
+            });
+        Self {
+            layout,
+            pipeline_layout,
+            shader,
+            capacity,
+            generation: None,
+            bind_group: None,
+        }
+    }
+    /// Rebuild the texture array after textures were added or destroyed.
+    fn update(&mut self, core: &NGCore) {
+        if self.generation == Some(core.texture_generation) {
+            return;
+        }
+        self.generation = Some(core.texture_generation);
+        let default = match core.textures.first() {
+            Some(texture) => texture,
+            None => return,
+        };
+        // Render targets are left out, they can't be sampled while being drawn into.
+        let textures = (0..self.capacity as usize)
+            .map(|i| match core.textures.get(i) {
+                Some(texture) if !texture.destroyed && texture.target.is_none() => texture,
+                _ => default,
+            })
+            .collect::<Vec<_>>();
+        let views = textures.iter().map(|t| &t.view).collect::<Vec<_>>();
+        let samplers = textures.iter().map(|t| &t.sampler).collect::<Vec<_>>();
+        self.bind_group = Some(core.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Array BG"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureViewArray(&views),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::SamplerArray(&samplers),
+                },
+            ],
+        }));
+    }
+    /// Whether an object can sample its texture from the array.
+    fn supports(&self, core: &NGCore, obj: &SSRObjectInfo) -> bool {
+        let texture = obj.texture.unwrap_or(0);
+        obj.material.is_none()
+            && texture < self.capacity as usize
+            && core
+                .textures
+                .get(texture)
+                .is_some_and(|t| !t.destroyed && t.target.is_none())
+    }
+}
+
+/// A GPU buffer kept between frames, written with queue.write_buffer and doubled in size when outgrown.
+struct GrowableBuffer {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+}
+impl GrowableBuffer {
+    fn new(core: &NGCore, label: &'static str, usage: wgpu::BufferUsages, size: u64) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        Self {
+            label,
+            usage,
+            buffer: Self::create(core, label, usage, size),
+        }
+    }
+    fn create(
+        core: &NGCore,
+        label: &'static str,
+        usage: wgpu::BufferUsages,
+        size: u64,
+    ) -> wgpu::Buffer {
+        core.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size.max(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+    /// Returns true when the buffer was replaced, bind groups holding it need to be rebuilt.
+    fn write(&mut self, core: &NGCore, data: &[u8]) -> bool {
+        let needed = data.len() as u64;
+        let grown = needed > self.buffer.size();
+        if grown {
+            let size = needed.next_power_of_two();
+            self.buffer = Self::create(core, self.label, self.usage, size);
+        }
+        if !data.is_empty() {
+            core.queue.write_buffer(&self.buffer, 0, data);
+        }
+        grown
+    }
+}
+
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2,  2 => Float32x4];
+const OBJECT_ATTRIBUTES: [wgpu::VertexAttribute; 1] = wgpu::vertex_attr_array![3 => Uint32];
+const VERTEX_BUFFERS: &[wgpu::VertexBufferLayout] = &[
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &VERTEX_ATTRIBUTES,
+    },
+    wgpu::VertexBufferLayout {
+        array_stride: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &OBJECT_ATTRIBUTES,
+    },
+];
+
+/// Everything that decides which render pipeline variant an object is drawn with.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+struct PipelineKey {
+    blend: BlendMode,
+    material: Option<Material>,
+    format: wgpu::TextureFormat,
+    samples: u32,
+    depth: bool,
+    stencil: StencilMode,
+    depth_only: bool,
+    bindless: bool,
+    pick: bool,
+}
+
+/// The clip rectangle in pixels clamped to the target, None when nothing of it is visible.
+fn scissor_rect(clip: Option<Rectangle>, size: (u32, u32)) -> Option<(u32, u32, u32, u32)> {
+    let clip = match clip {
+        Some(clip) => clip,
+        None => return Some((0, 0, size.0, size.1)),
+    };
+    let x1 = clip.top_left.x.round().clamp(0.0, size.0 as f32) as u32;
+    let y1 = clip.top_left.y.round().clamp(0.0, size.1 as f32) as u32;
+    let x2 = clip.bottom_right.x.round().clamp(0.0, size.0 as f32) as u32;
+    let y2 = clip.bottom_right.y.round().clamp(0.0, size.1 as f32) as u32;
+    (x2 > x1 && y2 > y1).then_some((x1, y1, x2 - x1, y2 - y1))
+}
+
+/// Where a frame is drawn: the output texture and the attachments that go with it.
+struct RenderDest<'a> {
+    output: &'a wgpu::Texture,
+    msaa: Option<&'a wgpu::Texture>,
+    depth: Option<&'a wgpu::Texture>,
+    clear: Option<wgpu::Color>,
+}
+
+/// The color pass being drawn, objects are drawn with the pipeline matching their state and this pass.
+#[derive(Copy, Clone, Debug)]
+struct DrawPass {
+    format: wgpu::TextureFormat,
+    size: (u32, u32),
+    samples: u32,
+    depth: bool,
+    replace: bool,
+    depth_only: bool,
+    /// The texture array is bound, objects that support it sample from it.
+    bindless: bool,
+    /// Writes object IDs for NGCore::pick instead of colors.
+    pick: bool,
+}
+impl DrawPass {
+    /// None when the object isn't drawn in this pass, masks need a stencil buffer.
+    fn key(&self, obj: &SSRObjectInfo) -> Option<PipelineKey> {
+        let stencil = match (self.depth, obj.stencil) {
+            (true, stencil) => stencil,
+            (false, StencilMode::Increment | StencilMode::Decrement) => return None,
+            (false, _) => StencilMode::Off,
+        };
+        if self.depth_only {
+            if obj.translucent && !obj.stencil.writes() {
+                return None;
+            }
+            return Some(PipelineKey {
+                blend: BlendMode::default(),
+                material: None,
+                format: self.format,
+                samples: self.samples,
+                depth: true,
+                stencil,
+                depth_only: true,
+                bindless: false,
+                pick: false,
+            });
+        }
+        if self.pick {
+            return Some(PipelineKey {
+                blend: BlendMode::Replace,
+                material: None,
+                format: self.format,
+                samples: self.samples,
+                depth: self.depth,
+                stencil,
+                depth_only: false,
+                bindless: false,
+                pick: true,
+            });
+        }
+        Some(PipelineKey {
+            blend: if self.replace {
+                BlendMode::Replace
+            } else {
+                obj.blend
+            },
+            material: obj.material,
+            format: self.format,
+            samples: self.samples,
+            depth: self.depth,
+            stencil,
+            depth_only: false,
+            bindless: self.bindless && obj.bindless,
+            pick: false,
+        })
+    }
 }
 impl SimpleShapeRenderPipeline {
-    const MAX: usize = 1_000_000;
+    const INITIAL_OBJECTS: usize = 1024;
     pub fn new(core: &NGCore) -> Self {
-        let max = SimpleShapeRenderPipeline::MAX;
         let get_msaa_tex = |sample_count: u32| -> wgpu::Texture {
             core.device.create_texture(&wgpu::TextureDescriptor {
                 label: Some("MultiSample Texture"),
@@ -156,30 +663,42 @@ impl SimpleShapeRenderPipeline {
             //MSAA::Enable16x => {Some(get_msaa_tex(16))}
         };
 
-        let vertex_buffer = core.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("SSR Vertex Buffer"),
-            size: 0,
-            usage: wgpu::BufferUsages::VERTEX,
-            mapped_at_creation: false,
-        });
-        let index_buffer = core.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("SSR Index Buffer"),
-            size: 0,
-            usage: wgpu::BufferUsages::INDEX,
-            mapped_at_creation: false,
-        });
-        let trans_buffer = core.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("SSR Trans Buffer"),
-            size: (max * std::mem::size_of::<SSRTransform>()) as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        let mats_buffer = core.device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("SSR Materials Buffer"),
-            size: (max * std::mem::size_of::<SSRMaterial>()) as wgpu::BufferAddress,
-            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let vertex_buffer = GrowableBuffer::new(
+            core,
+            "SSR Vertex Buffer",
+            wgpu::BufferUsages::VERTEX,
+            1 << 16,
+        );
+        let index_buffer =
+            GrowableBuffer::new(core, "SSR Index Buffer", wgpu::BufferUsages::INDEX, 1 << 16);
+        let object_buffer = GrowableBuffer::new(
+            core,
+            "SSR Object Buffer",
+            wgpu::BufferUsages::VERTEX,
+            1 << 14,
+        );
+        let zero_buffer =
+            GrowableBuffer::new(core, "SSR Zero Buffer", wgpu::BufferUsages::VERTEX, 1 << 14);
+        let storage = wgpu::BufferUsages::STORAGE;
+        let objects = Self::INITIAL_OBJECTS as u64;
+        let trans_buffer = GrowableBuffer::new(
+            core,
+            "SSR Trans Buffer",
+            storage,
+            objects * std::mem::size_of::<SSRTransform>() as u64,
+        );
+        let mats_buffer = GrowableBuffer::new(
+            core,
+            "SSR Materials Buffer",
+            storage,
+            objects * std::mem::size_of::<SSRMaterial>() as u64,
+        );
+        let params_buffer = GrowableBuffer::new(
+            core,
+            "SSR Material Params Buffer",
+            storage,
+            objects * std::mem::size_of::<SSRMaterialParams>() as u64,
+        );
         let data_bgl = core
             .device
             .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -205,28 +724,32 @@ impl SimpleShapeRenderPipeline {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
-        let data_bind_group = core.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Transforms Materials BG"),
-            layout: &data_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: trans_buffer.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: mats_buffer.as_entire_binding(),
-                },
-            ],
-        });
+        let data_bind_group =
+            Self::data_bind_group(core, &data_bgl, &trans_buffer, &mats_buffer, &params_buffer);
         let globals = GlobalUniforms::new(core, (32.0, 32.0));
         let shader = core
             .device
             .create_shader_module(wgpu::ShaderModuleDescriptor {
                 label: Some("SSR Shader"),
-                source: wgpu::ShaderSource::Wgsl(include_str!("shape_shader.wgsl").into()),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("shape_shader.wgsl"),
+                        include_str!("shape_fragment.wgsl")
+                    )
+                    .into(),
+                ),
             });
         let pipeline_layout = core
             .device
@@ -239,6 +762,29 @@ impl SimpleShapeRenderPipeline {
                 ],
                 push_constant_ranges: &[],
             });
+        let bindless = core.bindless.map(|capacity| {
+            Bindless::new(
+                core,
+                &[
+                    &globals.bind_group_layout,
+                    &data_bgl,
+                    &core.textures.first().expect("Texture").bind_group_layout,
+                ],
+                capacity,
+            )
+        });
+        let pick_shader = core
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("SSR Pick Shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    concat!(
+                        include_str!("shape_shader.wgsl"),
+                        include_str!("shape_pick.wgsl")
+                    )
+                    .into(),
+                ),
+            });
         let multisample_state = match &core.config.msaa {
             MSAA::Disabled => wgpu::MultisampleState::default(),
             MSAA::Enable4x => wgpu::MultisampleState {
@@ -253,108 +799,6 @@ impl SimpleShapeRenderPipeline {
             },
             //MSAA::Enable16x => {wgpu::MultisampleState { count: 16, mask: !0, alpha_to_coverage_enabled: true }}
         };
-        let vertex_state = wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[wgpu::VertexBufferLayout {
-                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2,  2 => Float32x4],
-            }],
-        };
-        let primitive_state = wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            unclipped_depth: false,
-            polygon_mode: wgpu::PolygonMode::Fill,
-            conservative: false,
-        };
-        let pipeline = core
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("SSR Pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: vertex_state.clone(),
-                primitive: primitive_state.clone(),
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth32FloatStencil8,
-                    depth_write_enabled: false,
-                    depth_compare: wgpu::CompareFunction::GreaterEqual,
-                    stencil: Default::default(),
-                    bias: Default::default(),
-                }),
-                multisample: multisample_state,
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: core.surface_configuration.format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-            });
-        let pipeline2 = core
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("SSR Pipeline Offscreen"),
-                layout: Some(&pipeline_layout),
-                vertex: vertex_state.clone(),
-                primitive: primitive_state.clone(),
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-            });
-        let pipeline3 = core
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("SSR Pipeline Offscreen"),
-                layout: Some(&pipeline_layout),
-                vertex: vertex_state.clone(),
-                primitive: primitive_state.clone(),
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                fragment: Some(wgpu::FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(wgpu::ColorTargetState {
-                        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-            });
-        let depth_pass = core
-            .device
-            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Depth Only Pass"),
-                layout: Some(&pipeline_layout),
-                vertex: vertex_state.clone(),
-                primitive: primitive_state.clone(),
-                depth_stencil: Some(wgpu::DepthStencilState {
-                    format: wgpu::TextureFormat::Depth32FloatStencil8,
-                    depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::GreaterEqual,
-                    stencil: Default::default(),
-                    bias: Default::default(),
-                }),
-                multisample: multisample_state,
-                fragment: None,
-                multiview: None,
-            });
         let depth_stencil = core.device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Depth Stencil Texture"),
             size: wgpu::Extent3d {
@@ -380,238 +824,456 @@ impl SimpleShapeRenderPipeline {
             index_buffer,
             trans_buffer,
             mats_buffer,
+            params_buffer,
+            data_bgl,
             data_bind_group,
             globals,
+            object_buffer,
+            zero_buffer,
             objects: vec![],
-            pipeline,
-            pipeline2,
-            pipeline3,
-            depth_pass,
+            shader,
+            pipeline_layout,
+            multisample_state,
+            surface_format: core.surface_configuration.format,
+            pipelines: HashMap::new(),
+            post: None,
+            bindless,
+            tags: vec![],
+            pick_shader,
+            picker: None,
         }
     }
-    fn render_to(
-        &mut self,
-        core: &mut NGCore,
-        texture: Option<&wgpu::Texture>,
-        render_target: Option<Image>,
-        replace: bool,
-    ) {
-        let disable_msaa = texture.is_none();
-        let texture = match (texture, render_target) {
-            (Some(texture), _) => texture,
-            (None, Some(image)) => {
-                if let Some(texture_info) = core.textures.get(image.texture) {
-                    &texture_info.texture
+    fn data_bind_group(
+        core: &NGCore,
+        layout: &wgpu::BindGroupLayout,
+        transforms: &GrowableBuffer,
+        materials: &GrowableBuffer,
+        params: &GrowableBuffer,
+    ) -> wgpu::BindGroup {
+        core.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Transforms Materials BG"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: transforms.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: materials.buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: params.buffer.as_entire_binding(),
+                },
+            ],
+        })
+    }
+    /// The most objects one frame can hold, limited by the largest per object storage buffer.
+    fn max_objects(&self, core: &NGCore) -> usize {
+        let limits = core.device.limits();
+        let largest = std::mem::size_of::<SSRTransform>()
+            .max(std::mem::size_of::<SSRMaterial>())
+            .max(std::mem::size_of::<SSRMaterialParams>());
+        (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size) as usize
+            / largest
+    }
+    fn create_pipeline(&self, core: &NGCore, key: PipelineKey) -> wgpu::RenderPipeline {
+        let masking = key.stencil.writes();
+        let depth_stencil = key.depth.then(|| wgpu::DepthStencilState {
+            format: wgpu::TextureFormat::Depth32FloatStencil8,
+            depth_write_enabled: key.depth_only && !masking,
+            depth_compare: if masking {
+                wgpu::CompareFunction::Always
+            } else {
+                wgpu::CompareFunction::GreaterEqual
+            },
+            stencil: key.stencil.state(),
+            bias: Default::default(),
+        });
+        let (shader, layout) = match (key.material, &self.bindless) {
+            _ if key.pick => (&self.pick_shader, &self.pipeline_layout),
+            (Some(material), _) => (&core.materials[material.id], &self.pipeline_layout),
+            (None, Some(bindless)) if key.bindless => (&bindless.shader, &bindless.pipeline_layout),
+            (None, _) => (&self.shader, &self.pipeline_layout),
+        };
+        let targets = [Some(wgpu::ColorTargetState {
+            format: key.format,
+            // Integer targets can't blend, the ID of whatever is drawn last wins.
+            blend: (!key.pick).then(|| key.blend.blend_state()),
+            write_mask: if masking {
+                wgpu::ColorWrites::empty()
+            } else {
+                wgpu::ColorWrites::ALL
+            },
+        })];
+        core.device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(if key.depth_only {
+                    "Depth Only Pass"
                 } else {
-                    return;
-                }
+                    "SSR Pipeline"
+                }),
+                layout: Some(layout),
+                vertex: Self::vertex_state(shader),
+                primitive: Self::primitive_state(),
+                depth_stencil,
+                multisample: Self::multisample_state(key.samples),
+                fragment: (!key.depth_only).then_some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: match key.material {
+                        _ if key.pick => "fs_pick",
+                        None if key.blend.premultiplied() => "fs_premultiplied",
+                        _ => "fs_main",
+                    },
+                    targets: &targets,
+                }),
+                multiview: None,
+            })
+    }
+    fn vertex_state(shader: &wgpu::ShaderModule) -> wgpu::VertexState<'_> {
+        wgpu::VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: VERTEX_BUFFERS,
+        }
+    }
+    fn primitive_state() -> wgpu::PrimitiveState {
+        wgpu::PrimitiveState {
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            unclipped_depth: false,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            conservative: false,
+        }
+    }
+    fn multisample_state(count: u32) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        }
+    }
+    /// Build any pipeline variants this frame's objects need, before a pass borrows them.
+    fn prepare_pipelines(&mut self, core: &NGCore, pass: DrawPass) {
+        let depth_pass = pass.depth.then_some(DrawPass {
+            depth_only: true,
+            ..pass
+        });
+        let mut keys = self
+            .objects
+            .iter()
+            .flat_map(|(_, obj)| [Some(pass), depth_pass].map(|p| p.and_then(|p| p.key(obj))))
+            .flatten()
+            .filter(|key| !self.pipelines.contains_key(key))
+            .collect::<Vec<_>>();
+        keys.dedup();
+        for key in keys {
+            if !self.pipelines.contains_key(&key) {
+                let pipeline = self.create_pipeline(core, key);
+                self.pipelines.insert(key, pipeline);
             }
-            (_, _) => return,
+        }
+    }
+    fn render_screen(&mut self, core: &NGCore, output: &wgpu::Texture) {
+        if let Some(bindless) = &mut self.bindless {
+            bindless.update(core);
+        }
+        let pass = DrawPass {
+            format: output.format(),
+            size: (output.width(), output.height()),
+            samples: self.multisample_state.count,
+            depth: true,
+            replace: false,
+            depth_only: false,
+            bindless: self
+                .bindless
+                .as_ref()
+                .is_some_and(|b| b.bind_group.is_some()),
+            pick: false,
         };
-
-        let output_view = texture.create_view(&TextureViewDescriptor::default());
-        let (view, resolve_target) = match &self.multisample {
-            Some(t) if !disable_msaa => (
-                t.create_view(&TextureViewDescriptor::default()),
-                Some(&output_view),
-            ),
-            _ => (output_view, None),
+        self.prepare_pipelines(core, pass);
+        let dest = RenderDest {
+            output,
+            msaa: self.multisample.as_ref(),
+            depth: Some(&self.depth_stencil),
+            clear: Some(core.config.clear_color.into()),
         };
+        self.render_to(core, &dest, pass);
+    }
+    /// Draw the screen's objects again as IDs and start reading back the pixel at pos.
+    fn render_pick(&mut self, core: &NGCore, pos: Vec2) -> Result<(), Option<Tag>> {
+        let size = (
+            core.surface_configuration.width,
+            core.surface_configuration.height,
+        );
+        if pos.x < 0.0 || pos.y < 0.0 || pos.x >= size.0 as f32 || pos.y >= size.1 as f32 {
+            return Err(None);
+        }
+        let mut picker = match self.picker.take() {
+            Some(picker) if (picker.ids.width(), picker.ids.height()) == size => picker,
+            _ => Picker::new(core, size),
+        };
+        let pass = DrawPass {
+            format: Picker::FORMAT,
+            size,
+            samples: 1,
+            depth: true,
+            replace: false,
+            depth_only: false,
+            bindless: false,
+            pick: true,
+        };
+        self.prepare_pipelines(core, pass);
+        let dest = RenderDest {
+            output: &picker.ids,
+            msaa: None,
+            depth: Some(&picker.depth),
+            clear: Some(wgpu::Color::TRANSPARENT),
+        };
+        self.render_to(core, &dest, pass);
+        let mut encoder = core
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Pick Readback Encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &picker.ids,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: pos.x as u32,
+                    y: pos.y as u32,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &picker.readback,
+                layout: wgpu::ImageDataLayout::default(),
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        core.queue.submit(std::iter::once(encoder.finish()));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        picker
+            .readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        picker.pending = Some((self.tags.clone(), receiver));
+        self.picker = Some(picker);
+        Ok(())
+    }
+    fn render_to(&self, core: &NGCore, dest: &RenderDest, pass: DrawPass) {
+        let output_view = dest.output.create_view(&TextureViewDescriptor::default());
+        let msaa_view = dest
+            .msaa
+            .map(|t| t.create_view(&TextureViewDescriptor::default()));
+        let (view, resolve_target) = match &msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&output_view)),
+            None => (&output_view, None),
+        };
+        let depth_view = dest
+            .depth
+            .map(|t| t.create_view(&TextureViewDescriptor::default()));
+        let mut encoder = core
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("SimpleShapeRenderPipeline Command Encoder"),
+            });
 
-        if !disable_msaa {
-            let depth_view = self
-                .depth_stencil
-                .create_view(&wgpu::TextureViewDescriptor::default());
-            let mut encoder = core
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("SimpleShapeRenderPipeline Command Encoder"),
-                });
-
+        if let Some(depth_view) = &depth_view {
             let mut depth_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Depth Pass"),
                 color_attachments: &[],
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_view,
+                    view: depth_view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Clear(0.0),
                         store: wgpu::StoreOp::Store,
                     }),
-                    stencil_ops: None,
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Store,
+                    }),
                 }),
                 timestamp_writes: None,
                 occlusion_query_set: None,
             });
 
-            depth_pass.set_pipeline(&self.depth_pass);
-
-            depth_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            depth_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            depth_pass.set_bind_group(0, &self.globals.bind_group, &[]);
-            depth_pass.set_bind_group(1, &self.data_bind_group, &[]);
-            depth_pass.set_bind_group(
-                2,
-                &core.textures.first().expect("Something").bind_group,
-                &[],
+            self.bind_defaults(core, &mut depth_pass);
+            self.draw_objects(
+                core,
+                &mut depth_pass,
+                DrawPass {
+                    depth_only: true,
+                    ..pass
+                },
             );
+        }
 
-            self.draw_objects(core, &mut depth_pass);
-
-            drop(depth_pass);
-
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target,
-                    ops: wgpu::Operations {
-                        load: if render_target.is_some() {
-                            wgpu::LoadOp::Load
-                        } else {
-                            wgpu::LoadOp::Clear(core.config.clear_color.into())
-                        },
-                        store: StoreOp::Store,
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: match dest.clear {
+                        Some(color) => wgpu::LoadOp::Clear(color),
+                        None => wgpu::LoadOp::Load,
                     },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_view,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: depth_view.as_ref().map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
                     depth_ops: Some(wgpu::Operations {
                         load: wgpu::LoadOp::Load,
                         store: wgpu::StoreOp::Store,
                     }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            if disable_msaa {
-                if replace {
-                    render_pass.set_pipeline(&self.pipeline3);
-                } else {
-                    render_pass.set_pipeline(&self.pipeline2);
-                }
-            } else {
-                render_pass.set_pipeline(&self.pipeline);
-            }
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.set_bind_group(0, &self.globals.bind_group, &[]);
-            render_pass.set_bind_group(1, &self.data_bind_group, &[]);
-            render_pass.set_bind_group(
-                2,
-                &core.textures.first().expect("Something").bind_group,
-                &[],
-            );
-
-            self.draw_objects(core, &mut render_pass);
-
-            drop(render_pass);
-            core.queue.submit(std::iter::once(encoder.finish()));
-        } else {
-            let mut encoder = core
-                .device
-                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                    label: Some("SimpleShapeRenderPipeline Command Encoder"),
-                });
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target,
-                    ops: wgpu::Operations {
-                        load: if render_target.is_some() {
-                            wgpu::LoadOp::Load
-                        } else {
-                            wgpu::LoadOp::Clear(core.config.clear_color.into())
-                        },
-                        store: StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            if disable_msaa {
-                if replace {
-                    render_pass.set_pipeline(&self.pipeline3);
-                } else {
-                    render_pass.set_pipeline(&self.pipeline2);
+                    // Masks are replayed in order, so start from a clean stencil again.
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Store,
+                    }),
                 }
-            } else {
-                render_pass.set_pipeline(&self.pipeline);
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.bind_defaults(core, &mut render_pass);
+        if pass.bindless {
+            if let Some(group) = self.bindless.as_ref().and_then(|b| b.bind_group.as_ref()) {
+                render_pass.set_bind_group(3, group, &[]);
             }
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.set_bind_group(0, &self.globals.bind_group, &[]);
-            render_pass.set_bind_group(1, &self.data_bind_group, &[]);
-            render_pass.set_bind_group(
-                2,
-                &core.textures.first().expect("Something").bind_group,
-                &[],
-            );
-
-            self.draw_objects(core, &mut render_pass);
-
-            drop(render_pass);
-            core.queue.submit(std::iter::once(encoder.finish()));
         }
+        self.draw_objects(core, &mut render_pass, pass);
+
+        drop(render_pass);
+        core.queue.submit(std::iter::once(encoder.finish()));
+    }
+    fn bind_defaults<'pass, 'draw: 'pass>(
+        &'draw self,
+        core: &'pass NGCore,
+        pass: &mut wgpu::RenderPass<'pass>,
+    ) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+        pass.set_vertex_buffer(1, self.object_buffer.buffer.slice(..));
+        pass.set_index_buffer(
+            self.index_buffer.buffer.slice(..),
+            wgpu::IndexFormat::Uint32,
+        );
+        pass.set_bind_group(0, &self.globals.bind_group, &[]);
+        pass.set_bind_group(1, &self.data_bind_group, &[]);
+        pass.set_bind_group(
+            2,
+            &core.textures.first().expect("Something").bind_group,
+            &[],
+        );
     }
 
+    /// Draws the objects in order, consecutive immediate objects sharing a pipeline, texture,
+    /// clip and stencil reference are merged into a single draw.
     fn draw_objects<'pass, 'draw: 'pass>(
         &'draw self,
         core: &'pass NGCore,
         pass: &mut wgpu::RenderPass<'pass>,
+        draw: DrawPass,
     ) {
-        for (i, obj) in self.objects.iter().enumerate() {
-            let index = i as u32..i as u32 + 1;
-            match obj.bo_slot {
-                None => {
-                    if let Some(tex) = obj.texture {
-                        pass.set_bind_group(2, &core.textures[tex].bind_group, &[]);
-                    } else {
-                        pass.set_bind_group(
-                            2,
-                            &core.textures.first().expect("Something").bind_group,
-                            &[],
-                        );
+        let mut current = None;
+        let mut reference = None;
+        let mut clip = None;
+        let mut texture = None;
+        let mut batch: Option<std::ops::Range<u32>> = None;
+        for (i, obj) in self.objects.iter() {
+            let key = match draw.key(obj) {
+                Some(key) => key,
+                None => continue,
+            };
+            let scissor = match scissor_rect(obj.clip, draw.size) {
+                Some(scissor) => scissor,
+                None => continue,
+            };
+            let vb = match obj.bo_slot {
+                None => None,
+                Some(vbi) => match core.mesh_buffers.get(vbi) {
+                    Some(vb) => Some(vb),
+                    None => {
+                        error!("MeshBuffer index {:?} out of bounds.", vbi);
+                        continue;
                     }
-                    pass.draw_indexed(
-                        obj.start_index..obj.end_index,
-                        obj.start_vertice as i32,
-                        index,
-                    );
+                },
+            };
+            let tex = obj.texture.unwrap_or(0);
+            let same_state = current == Some(key)
+                && clip == Some(scissor)
+                && (key.bindless || texture == Some(tex))
+                && (!draw.depth || reference == Some(obj.stencil_ref));
+            if let Some(range) = &mut batch {
+                if same_state && vb.is_none() && range.end == obj.start_index {
+                    range.end = obj.end_index;
+                    continue;
                 }
-                Some(vbi) => {
-                    let vb = match core.mesh_buffers.get(vbi) {
-                        Some(vb) => vb,
-                        None => {
-                            error!("MeshBuffer index {:?} out of bounds.", vbi);
-                            return;
-                        }
-                    };
-                    if let Some(tex) = vb.texture {
-                        pass.set_bind_group(2, &core.textures[tex].bind_group, &[]);
-                    } else {
-                        pass.set_bind_group(
-                            2,
-                            &core.textures.first().expect("Something").bind_group,
-                            &[],
-                        );
+            }
+            if let Some(range) = batch.take() {
+                pass.draw_indexed(range, 0, 0..1);
+            }
+            if clip != Some(scissor) {
+                pass.set_scissor_rect(scissor.0, scissor.1, scissor.2, scissor.3);
+                clip = Some(scissor);
+            }
+            if current != Some(key) {
+                match self.pipelines.get(&key) {
+                    Some(pipeline) => pass.set_pipeline(pipeline),
+                    None => {
+                        error!("Missing pipeline for {:?}", key);
+                        continue;
+                    }
+                }
+                current = Some(key);
+            }
+            if draw.depth && reference != Some(obj.stencil_ref) {
+                pass.set_stencil_reference(obj.stencil_ref);
+                reference = Some(obj.stencil_ref);
+            }
+            if !key.bindless && texture != Some(tex) {
+                match core.textures.get(tex) {
+                    Some(t) => pass.set_bind_group(2, &t.bind_group, &[]),
+                    None => {
+                        error!("Texture index {:?} out of bounds.", tex);
+                        continue;
                     }
+                }
+                texture = Some(tex);
+            }
+            match vb {
+                None => batch = Some(obj.start_index..obj.end_index),
+                Some(vb) => {
                     pass.set_vertex_buffer(0, vb.vertex_buffer.slice(..));
+                    pass.set_vertex_buffer(1, self.zero_buffer.buffer.slice(..));
                     pass.set_index_buffer(vb.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-                    pass.draw_indexed(
-                        0..(vb.index_buffer.size() as u32 / std::mem::size_of::<i32>() as u32),
-                        0,
-                        index,
+                    pass.draw_indexed(obj.start_index..obj.end_index, 0, *i..*i + 1);
+                    pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+                    pass.set_vertex_buffer(1, self.object_buffer.buffer.slice(..));
+                    pass.set_index_buffer(
+                        self.index_buffer.buffer.slice(..),
+                        wgpu::IndexFormat::Uint32,
                     );
-                    pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-                    pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
                 }
             }
         }
+        if let Some(range) = batch {
+            pass.draw_indexed(range, 0, 0..1);
+        }
     }
 
     // pub fn my_render(
@@ -698,8 +1360,8 @@ impl SimpleShapeRenderPipeline {
     //     } else {
     //         render_pass.set_pipeline(&self.pipeline);
     //     }
-    //     render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-    //     render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    //     render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
+    //     render_pass.set_index_buffer(self.index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint32);
     //     render_pass.set_bind_group(0, &self.globals.bind_group, &[]);
     //     render_pass.set_bind_group(1, &self.data_bind_group, &[]);
     //     render_pass.set_bind_group(
@@ -752,9 +1414,9 @@ impl SimpleShapeRenderPipeline {
     //                     0,
     //                     index,
     //                 );
-    //                 render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+    //                 render_pass.set_vertex_buffer(0, self.vertex_buffer.buffer.slice(..));
     //                 render_pass
-    //                     .set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+    //                     .set_index_buffer(self.index_buffer.buffer.slice(..), wgpu::IndexFormat::Uint32);
     //             }
     //         }
     //     }
@@ -766,9 +1428,39 @@ impl NGRenderPipeline for SimpleShapeRenderPipeline {
     fn render(&mut self, core: &mut NGCore) -> Result<(), NGError> {
         match core.surface.get_current_texture() {
             Result::Ok(surface_texture) => {
-                self.render_to(core, Some(&surface_texture.texture), None, false);
+                if core.post_effects.is_empty() {
+                    self.render_screen(core, &surface_texture.texture);
+                } else {
+                    let mut post = self.post.take().unwrap_or_else(|| {
+                        PostProcessor::new(
+                            core,
+                            &self.globals.bind_group_layout,
+                            self.surface_format,
+                        )
+                    });
+                    self.render_screen(core, post.scene(core));
+                    let effects = core.post_effects.clone();
+                    post.apply(
+                        core,
+                        &self.globals.bind_group,
+                        &effects,
+                        &surface_texture.texture,
+                    );
+                    self.post = Some(post);
+                }
                 core.window.pre_present_notify();
                 surface_texture.present();
+                if let Some(picked) = self.picker.as_mut().and_then(|p| p.poll(core)) {
+                    core.picked = picked;
+                }
+                let waiting = self.picker.as_ref().is_some_and(|p| p.pending.is_some());
+                if !waiting {
+                    if let Some(pos) = core.pick_request.take() {
+                        if let Err(picked) = self.render_pick(core, pos) {
+                            core.picked = picked;
+                        }
+                    }
+                }
             }
             Result::Err(_err) => match core.window.is_minimized() {
                 None => {
@@ -787,39 +1479,130 @@ impl NGRenderPipeline for SimpleShapeRenderPipeline {
         Ok(())
     }
     fn render_image(&mut self, core: &mut NGCore, texture: Image, replace: bool) {
-        self.render_to(core, None, Some(texture), replace);
+        let output = match core.textures.get(texture.texture) {
+            Some(texture_info) => &texture_info.texture,
+            None => return,
+        };
+        let pass = DrawPass {
+            format: output.format(),
+            size: (output.width(), output.height()),
+            samples: 1,
+            depth: false,
+            replace,
+            depth_only: false,
+            bindless: false,
+            pick: false,
+        };
+        self.prepare_pipelines(core, pass);
+        let dest = RenderDest {
+            output,
+            msaa: None,
+            depth: None,
+            clear: None,
+        };
+        self.render_to(core, &dest, pass);
+    }
+    fn render_target(&mut self, core: &mut NGCore, target: Image, clear: Option<Color>) {
+        let texture_info = match core.textures.get(target.texture) {
+            Some(texture_info) => texture_info,
+            None => return,
+        };
+        let output = &texture_info.texture;
+        let (msaa, depth) = match &texture_info.target {
+            Some(target) => (target.msaa.as_ref(), Some(&target.depth)),
+            None => (None, None),
+        };
+        let pass = DrawPass {
+            format: output.format(),
+            size: (output.width(), output.height()),
+            samples: msaa.map_or(1, |t| t.sample_count()),
+            depth: depth.is_some(),
+            replace: false,
+            depth_only: false,
+            bindless: false,
+            pick: false,
+        };
+        self.prepare_pipelines(core, pass);
+        let dest = RenderDest {
+            output,
+            msaa,
+            depth,
+            clear: clear.map(|c| c.into()),
+        };
+        self.render_to(core, &dest, pass);
     }
     fn set_data(&mut self, core: &mut NGCore, data: Box<dyn std::any::Any>) {
         let rd = data.downcast::<SSRRenderData>().expect("Get Render Data");
         let data = *rd;
 
+        let mut data = data;
+        let max_objects = self.max_objects(core);
+        if data.object_info.len() > max_objects {
+            error!(
+                "{} objects drawn, only the first {} fit in a storage buffer on this device.",
+                data.object_info.len(),
+                max_objects
+            );
+            data.object_info.truncate(max_objects);
+            data.transforms.truncate(max_objects);
+            data.materials.truncate(max_objects);
+            data.params.truncate(max_objects);
+            data.tags.truncate(max_objects);
+        }
         self.objects.clear();
-        self.objects.extend(data.object_info);
-
-        self.vertex_buffer = core
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Simple Shape Renderer Pipeline Vertex Buffer"),
-                contents: bytemuck::cast_slice(data.vertices.as_slice()),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-        self.index_buffer = core
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Simple Shape Renderer Pipeline Index Buffer"),
-                contents: bytemuck::cast_slice(data.indices.as_slice()),
-                usage: wgpu::BufferUsages::INDEX,
-            });
+        self.objects
+            .extend((0..).zip(data.object_info.iter().copied()));
+        self.tags.clone_from(&data.tags);
+        if let Some(bindless) = &self.bindless {
+            for (_, obj) in self.objects.iter_mut() {
+                obj.bindless = bindless.supports(core, obj);
+            }
+        }
+        if data.sort {
+            sort_objects(&mut self.objects);
+        }
+        order_translucent(&mut self.objects);
+        let (vertices, indices, object_ids) = batch_vertices(&mut self.objects, &data);
 
+        self.vertex_buffer
+            .write(core, bytemuck::cast_slice(vertices.as_slice()));
+        self.index_buffer
+            .write(core, bytemuck::cast_slice(indices.as_slice()));
+        self.object_buffer
+            .write(core, bytemuck::cast_slice(object_ids.as_slice()));
+        let buffered_vertices = self
+            .objects
+            .iter()
+            .filter_map(|(_, obj)| obj.bo_slot)
+            .filter_map(|slot| core.mesh_buffers.get(slot))
+            .map(|vb| vb.vertex_buffer.size() / std::mem::size_of::<Vertex>() as u64)
+            .max()
+            .unwrap_or(0);
+        let zeros = buffered_vertices * std::mem::size_of::<u32>() as u64;
+        if zeros > self.zero_buffer.buffer.size() {
+            self.zero_buffer.write(core, &vec![0; zeros as usize]);
+        }
+        let grown = [
+            self.trans_buffer
+                .write(core, bytemuck::cast_slice(data.transforms.as_slice())),
+            self.mats_buffer
+                .write(core, bytemuck::cast_slice(data.materials.as_slice())),
+            self.params_buffer
+                .write(core, bytemuck::cast_slice(data.params.as_slice())),
+        ];
+        if grown.contains(&true) {
+            self.data_bind_group = Self::data_bind_group(
+                core,
+                &self.data_bgl,
+                &self.trans_buffer,
+                &self.mats_buffer,
+                &self.params_buffer,
+            );
+        }
         core.queue.write_buffer(
-            &self.trans_buffer,
-            0,
-            bytemuck::cast_slice(data.transforms.as_slice()),
-        );
-        core.queue.write_buffer(
-            &self.mats_buffer,
+            &self.globals.view_buffer,
             0,
-            bytemuck::cast_slice(data.materials.as_slice()),
+            bytemuck::bytes_of(&data.view.unwrap_or_default()),
         );
     }
 
@@ -886,7 +1669,121 @@ pub struct SSRRenderData {
     indices: Vec<u32>,
     transforms: Vec<SSRTransform>,
     materials: Vec<SSRMaterial>,
+    params: Vec<SSRMaterialParams>,
+    tags: Vec<Option<u32>>,
     object_info: Vec<SSRObjectInfo>,
+    masks: Vec<MaskLayer>,
+    clips: Vec<Rectangle>,
+    view: Option<ViewUniform>,
+    sort: bool,
+}
+
+/// Stable sorts runs of immediate objects that share blend, material, mask and clip state
+/// by texture and depth, so more of them end up next to each other in one draw.
+fn sort_objects(objects: &mut [(u32, SSRObjectInfo)]) {
+    let same_layer = |a: &SSRObjectInfo, b: &SSRObjectInfo| {
+        a.bo_slot.is_none()
+            && b.bo_slot.is_none()
+            && a.blend == b.blend
+            && a.material == b.material
+            && a.stencil == b.stencil
+            && a.stencil_ref == b.stencil_ref
+            && a.clip == b.clip
+    };
+    let mut start = 0;
+    while start < objects.len() {
+        let mut end = start + 1;
+        while end < objects.len() && same_layer(&objects[start].1, &objects[end].1) {
+            end += 1;
+        }
+        objects[start..end]
+            .sort_by(|(_, a), (_, b)| a.texture.cmp(&b.texture).then(a.depth.total_cmp(&b.depth)));
+        start = end;
+    }
+}
+
+/// Moves translucent objects after the opaque ones and sorts them back to front, opaque objects
+/// keep their order since the depth pre-pass sorts them out. An opaque object at the same depth as
+/// an earlier translucent one moves along with it so it's still drawn on top. Objects writing a
+/// mask stay where they are, so everything keeps being drawn under the same mask as before.
+fn order_translucent(objects: &mut [(u32, SSRObjectInfo)]) {
+    for segment in objects.split_mut(|(_, obj)| obj.stencil.writes()) {
+        let mut depths = HashSet::new();
+        let (mut opaque, mut translucent) = (vec![], vec![]);
+        for object in segment.iter() {
+            let depth = object.1.depth.to_bits();
+            if object.1.translucent {
+                depths.insert(depth);
+                translucent.push(*object);
+            } else if depths.contains(&depth) {
+                translucent.push(*object);
+            } else {
+                opaque.push(*object);
+            }
+        }
+        translucent.sort_by(|(_, a), (_, b)| a.depth.total_cmp(&b.depth));
+        for (slot, object) in segment
+            .iter_mut()
+            .zip(opaque.into_iter().chain(translucent))
+        {
+            *slot = object;
+        }
+    }
+}
+
+/// Whether any vertex of a mesh is see-through.
+pub(crate) fn mesh_translucent(vertices: &[Vertex]) -> bool {
+    vertices.iter().any(|v| v.a < 1.0)
+}
+
+/// Lays the immediate objects' vertices and indices out in draw order, with absolute indices and
+/// the index of each vertex's object, so consecutive objects can be drawn together.
+fn batch_vertices(
+    objects: &mut [(u32, SSRObjectInfo)],
+    data: &SSRRenderData,
+) -> (Vec<Vertex>, Vec<u32>, Vec<u32>) {
+    let mut vertices = Vec::with_capacity(data.vertices.len());
+    let mut indices = Vec::with_capacity(data.indices.len());
+    let mut object_ids = Vec::with_capacity(data.vertices.len());
+    for (i, obj) in objects.iter_mut() {
+        if obj.bo_slot.is_some() {
+            continue;
+        }
+        let base = vertices.len() as u32;
+        let start = indices.len() as u32;
+        let source = obj.start_vertice as usize..obj.end_vertice as usize;
+        vertices.extend_from_slice(&data.vertices[source.clone()]);
+        object_ids.extend(std::iter::repeat_n(*i, source.len()));
+        indices.extend(
+            data.indices[obj.start_index as usize..obj.end_index as usize]
+                .iter()
+                .map(|index| index + base),
+        );
+        obj.start_vertice = base;
+        obj.end_vertice = vertices.len() as u32;
+        obj.start_index = start;
+        obj.end_index = indices.len() as u32;
+    }
+    (vertices, indices, object_ids)
+}
+
+/// The frontmost z of a mesh.
+pub(crate) fn mesh_depth(vertices: &[Vertex]) -> f32 {
+    vertices
+        .iter()
+        .map(|v| v.z)
+        .fold(None, |depth: Option<f32>, z| {
+            Some(depth.map_or(z, |d| d.max(z)))
+        })
+        .unwrap_or(0.0)
+}
+
+/// A mask on the ShapeGfx mask stack, the objects from start to end write it.
+#[derive(Copy, Clone, Debug)]
+struct MaskLayer {
+    start: usize,
+    end: Option<usize>,
+    mode: MaskMode,
 }
 
 impl SSRRenderData {
@@ -896,7 +1793,29 @@ impl SSRRenderData {
             indices: vec![],
             transforms: vec![],
             materials: vec![],
+            params: vec![],
+            tags: vec![],
             object_info: vec![],
+            masks: vec![],
+            clips: vec![],
+            view: None,
+            sort: false,
+        }
+    }
+    /// Stencil state for the next object from the mask stack.
+    fn stencil(&self) -> (StencilMode, u32) {
+        let level = self.masks.len() as u32;
+        match self.masks.last() {
+            None => (StencilMode::Off, 0),
+            Some(MaskLayer { end: None, .. }) => (StencilMode::Increment, level - 1),
+            Some(MaskLayer {
+                mode: MaskMode::Inside,
+                ..
+            }) => (StencilMode::Test, level),
+            Some(MaskLayer {
+                mode: MaskMode::Outside,
+                ..
+            }) => (StencilMode::Test, level - 1),
         }
     }
 }
@@ -907,6 +1826,87 @@ impl AsRef<SSRRenderData> for SSRRenderData {
     }
 }
 
+/// How ShapeGfx::draw_sprite places an image. pos is where origin ends up, origin is in pixels
+/// of the sprite and is also what it's scaled and rotated around.
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteParams {
+    pub pos: Vec2,
+    pub origin: Vec2,
+    pub scale: Vec2,
+    pub rotation: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub tint: Color,
+    pub depth: f32,
+    /// Only draw this part of the image, in pixels of the image.
+    pub sub_rect: Option<Rectangle>,
+}
+impl Default for SpriteParams {
+    fn default() -> Self {
+        Self {
+            pos: Vec2::ZERO,
+            origin: Vec2::ZERO,
+            scale: Vec2::new(1, 1),
+            rotation: 0.0,
+            flip_x: false,
+            flip_y: false,
+            tint: Color::WHITE,
+            depth: 0.0,
+            sub_rect: None,
+        }
+    }
+}
+impl SpriteParams {
+    pub fn at(pos: Vec2) -> Self {
+        Self {
+            pos,
+            ..Default::default()
+        }
+    }
+}
+
+/// The corners of a sprite quad, clockwise from the top left, and its indices.
+fn sprite_quad(image: &Image, params: &SpriteParams) -> ([Vertex; 4], [u32; 6]) {
+    let image = match params.sub_rect {
+        Some(rect) => {
+            let start = image.sub_image.map_or(Vec2::ZERO, |(start, _)| start);
+            image.sub_image(start + rect.top_left, rect.size())
+        }
+        None => *image,
+    };
+    let size = image.size();
+    let (mut min, mut max) = image.get_uv();
+    if params.flip_x {
+        std::mem::swap(&mut min.x, &mut max.x);
+    }
+    if params.flip_y {
+        std::mem::swap(&mut min.y, &mut max.y);
+    }
+    let (sin, cos) = params.rotation.sin_cos();
+    let corner = |x: f32, y: f32, u: f32, v: f32| {
+        let local = (Vec2::new(x, y) - params.origin) * params.scale;
+        let rotated = Vec2::new(local.x * cos - local.y * sin, local.x * sin + local.y * cos);
+        let p = params.pos + rotated;
+        Vertex {
+            z: params.depth,
+            ..Vertex::new(p.x, p.y).uv(u, v).rgba(params.tint)
+        }
+    };
+    let vertices = [
+        corner(0.0, 0.0, min.x, min.y),
+        corner(size.x, 0.0, max.x, min.y),
+        corner(size.x, size.y, max.x, max.y),
+        corner(0.0, size.y, min.x, max.y),
+    ];
+    // A negative scale on one axis mirrors the quad, which would otherwise get culled.
+    let indices = if params.scale.x * params.scale.y < 0.0 {
+        [0, 1, 2, 0, 2, 3]
+    } else {
+        [2, 1, 0, 3, 2, 0]
+    };
+    (vertices, indices)
+}
+
 pub struct ShapeGfx<'draw> {
     core: &'draw mut NGCore,
     data: SSRRenderData,
@@ -915,9 +1915,79 @@ pub struct ShapeGfx<'draw> {
     rotation_origin: Vec2,
     tint: Color,
     depth: f32,
+    blend: BlendMode,
+    material: Option<(Material, SSRMaterialParams)>,
+    target: Option<(Image, Option<Color>)>,
+    screen: Option<SSRRenderData>,
+    camera: bool,
+    transform: Matrix3x3,
+    transforms: Vec<Matrix3x3>,
+    translucent: Option<bool>,
+    tag: Option<u32>,
 }
 
 impl<'draw> ShapeGfx<'draw> {
+    pub(crate) fn core(&mut self) -> &mut NGCore {
+        self.core
+    }
+    /// Save the current transform, like MeshBuilder::push.
+    pub fn push_transform(&mut self) {
+        self.transforms.push(self.transform);
+    }
+    pub fn pop_transform(&mut self) {
+        match self.transforms.pop() {
+            Some(transform) => self.transform = transform,
+            None => warn!("No transform previously stored."),
+        }
+    }
+    /// Multiply m onto the current transform, affecting later draws until it's changed or popped.
+    pub fn transform(&mut self, m: Matrix3x3) {
+        self.transform = self.transform * m;
+    }
+    pub fn set_transform(&mut self, m: Matrix3x3) {
+        self.transform = m;
+    }
+    pub fn reset_transform(&mut self) {
+        self.transform = Matrix3x3::default();
+    }
+    pub fn translate(&mut self, t: Vec2) {
+        self.transform(Matrix3x3::translation(t));
+    }
+    pub fn scale(&mut self, s: Vec2) {
+        self.transform(Matrix3x3::scale(s));
+    }
+    pub fn skew(&mut self, angles: Vec2) {
+        self.transform(Matrix3x3::skew(angles));
+    }
+    /// View everything drawn after this through camera, until clear_camera.
+    /// There is one camera per screen or render target, setting it again moves earlier draws too.
+    pub fn set_camera(&mut self, camera: &Camera) {
+        self.data.view = Some(camera.view());
+        self.camera = true;
+    }
+    /// Draw in screen space again, for things like a HUD on top of the world.
+    pub fn clear_camera(&mut self) {
+        self.camera = false;
+    }
+    /// Draw with a custom material from NGCore::create_material, params are up to 16 floats.
+    pub fn set_material(&mut self, material: Material, params: &[f32]) {
+        self.material = Some((material, SSRMaterialParams::new(params)));
+    }
+    pub fn clear_material(&mut self) {
+        self.material = None;
+    }
+    pub fn set_blend_mode(&mut self, blend: BlendMode) {
+        self.blend = blend;
+    }
+    /// Force following draws to be treated as translucent or opaque. With None, the default,
+    /// it's decided from the tint, vertex colors, texture alpha and blend mode.
+    pub fn set_translucent(&mut self, translucent: Option<bool>) {
+        self.translucent = translucent;
+    }
+    /// What NGCore::pick reports for following draws, None reports their object index.
+    pub fn set_tag(&mut self, tag: Option<u32>) {
+        self.tag = tag;
+    }
     pub fn set_depth(&mut self, depth: f32) {
         self.depth = depth;
     }
@@ -955,6 +2025,15 @@ impl<'draw> ShapeGfx<'draw> {
             rotation_origin: Vec2::ZERO,
             tint: Color::WHITE,
             depth: 0.0,
+            blend: BlendMode::default(),
+            material: None,
+            target: None,
+            screen: None,
+            camera: false,
+            transform: Matrix3x3::default(),
+            transforms: vec![],
+            translucent: None,
+            tag: None,
         }
     }
     pub fn draw_image(&mut self, image: &Image, pos: Vec2) {
@@ -969,6 +2048,37 @@ impl<'draw> ShapeGfx<'draw> {
         mesh.set_z_depth(self.depth);
         self.draw_mesh(&mesh, pos);
     }
+    /// Draw image as a nine-slice filling rect, for panels and buttons with borders that shouldn't stretch.
+    pub fn draw_nine_slice(&mut self, image: &Image, slice: NineSlice, rect: Rectangle) {
+        let mut mesh = nine_slice(image, slice, rect.top_left, rect.bottom_right);
+        mesh.set_z_depth(self.depth);
+        self.draw_mesh(&mesh, Vec2::ZERO);
+    }
+    /// Draw an image as a single quad, written straight into the frame without building a Mesh.
+    pub fn draw_sprite(&mut self, image: &Image, params: SpriteParams) {
+        let (vertices, indices) = sprite_quad(image, &params);
+        let start_vertex = self.data.vertices.len();
+        let start_index = self.data.indices.len();
+        self.data.vertices.extend_from_slice(&vertices);
+        self.data.indices.extend_from_slice(&indices);
+        let info = SSRObjectInfo {
+            bo_slot: None,
+            start_vertice: start_vertex as u32,
+            end_vertice: self.data.vertices.len() as u32,
+            depth: params.depth,
+            translucent: params.tint.a < 1.0,
+            start_index: start_index as u32,
+            end_index: self.data.indices.len() as u32,
+            texture: image.texture_id(),
+            blend: self.blend,
+            material: None,
+            stencil: StencilMode::Off,
+            stencil_ref: 0,
+            clip: None,
+            bindless: false,
+        };
+        self.push_object(info, Vec2::ZERO, 1);
+    }
     pub fn draw_mesh(&mut self, mesh: &Mesh, pos: Vec2) {
         match (mesh.buffer.get(), mesh.dirty.get(), mesh.buffer_id.get()) {
             (true, _, None) => {
@@ -986,86 +2096,456 @@ impl<'draw> ShapeGfx<'draw> {
                 self.draw_buffer(bid, pos);
             }
             (false, _, _) => {
-                let texture = match mesh.image {
-                    Some(image) => {
-                        if let Some(atlas) = image.atlas {
-                            Some(atlas.0)
-                        } else {
-                            Some(image.texture)
-                        }
-                    }
-                    None => None,
-                };
-                let start_vertex = self.data.vertices.len();
-                let start_index = self.data.indices.len();
-                self.data.vertices.extend(mesh.vertices.as_slice());
-                self.data.indices.extend(mesh.indices.as_slice());
-                let end_index = self.data.indices.len();
-                let info = SSRObjectInfo {
-                    bo_slot: None,
-                    start_vertice: start_vertex as u32,
-                    start_index: start_index as u32,
-                    end_index: end_index as u32,
-                    texture,
-                };
-                let transform = SSRTransform {
-                    x: self.offset.x + pos.x,
-                    y: self.offset.y + pos.y,
-                    r: self.rotation,
-                    rx: self.rotation_origin.x,
-                    ry: self.rotation_origin.y,
-                };
-                let material = SSRMaterial {
-                    kind: if mesh.image.is_none() { 0 } else { 1 },
-                    r: self.tint.r,
-                    g: self.tint.g,
-                    b: self.tint.b,
-                    a: self.tint.a,
-                };
-                self.data.transforms.push(transform);
-                self.data.materials.push(material);
-                self.data.object_info.push(info);
+                for (range, image) in mesh.texture_parts() {
+                    self.draw_mesh_part(mesh, range, image, pos);
+                }
             }
         }
     }
+    /// draw_mesh with a tag for NGCore::pick, the tag set with set_tag is kept for later draws.
+    pub fn draw_mesh_tagged(&mut self, mesh: &Mesh, pos: Vec2, tag: u32) {
+        let previous = self.tag.replace(tag);
+        self.draw_mesh(mesh, pos);
+        self.tag = previous;
+    }
+    /// One object per image a mesh uses, with only the vertices that part references.
+    fn draw_mesh_part(
+        &mut self,
+        mesh: &Mesh,
+        range: std::ops::Range<u32>,
+        image: Option<Image>,
+        pos: Vec2,
+    ) {
+        let indices = &mesh.indices[range.start as usize..range.end as usize];
+        let (first, last) = match (indices.iter().min(), indices.iter().max()) {
+            (Some(&first), Some(&last)) if (last as usize) < mesh.vertices.len() => (first, last),
+            (None, _) | (_, None) => return,
+            _ => {
+                warn!(
+                    "Mesh indices point past its {} vertices",
+                    mesh.vertices.len()
+                );
+                return;
+            }
+        };
+        let vertices = &mesh.vertices[first as usize..=last as usize];
+        let start_vertex = self.data.vertices.len();
+        let start_index = self.data.indices.len();
+        self.data.vertices.extend_from_slice(vertices);
+        self.data.indices.extend(indices.iter().map(|i| i - first));
+        let info = SSRObjectInfo {
+            bo_slot: None,
+            start_vertice: start_vertex as u32,
+            end_vertice: self.data.vertices.len() as u32,
+            depth: mesh_depth(vertices),
+            translucent: mesh_translucent(vertices),
+            start_index: start_index as u32,
+            end_index: self.data.indices.len() as u32,
+            texture: image.and_then(|image| image.texture_id()),
+            blend: self.blend,
+            material: None,
+            stencil: StencilMode::Off,
+            stencil_ref: 0,
+            clip: None,
+            bindless: false,
+        };
+        self.push_object(info, pos, if image.is_none() { 0 } else { 1 });
+    }
     pub fn draw_buffer(&mut self, buffer_id: usize, pos: Vec2) {
-        match self.core.buffered_objects.get_mut(buffer_id) {
-            Some(info) => {
-                let transform = SSRTransform {
-                    x: self.offset.x + pos.x,
-                    y: self.offset.y + pos.y,
-                    r: self.rotation,
-                    rx: self.rotation_origin.x,
-                    ry: self.rotation_origin.y,
-                };
-                let material = SSRMaterial {
-                    kind: 0,
-                    r: self.tint.r,
-                    g: self.tint.g,
-                    b: self.tint.b,
-                    a: self.tint.a,
-                };
-
-                self.data.transforms.push(transform);
-                self.data.materials.push(material);
-                self.data.object_info.push(*info);
+        match self.core.buffered_objects.get(buffer_id) {
+            Some(parts) => {
+                for info in parts.clone() {
+                    self.push_object(info, pos, 0);
+                }
             }
             None => {
                 warn!("No buffer at index {:?}", buffer_id)
             }
         }
     }
+    /// Adds an object with the current transform, tint, blend, material and mask state.
+    fn push_object(&mut self, info: SSRObjectInfo, pos: Vec2, kind: i32) {
+        let (stencil, stencil_ref) = self.data.stencil();
+        self.data.transforms.push(
+            SSRTransform {
+                camera: self.camera as u32,
+                ..SSRTransform::new(self.offset + pos, self.rotation, self.rotation_origin)
+            }
+            .with_matrix(self.transform),
+        );
+        self.data.materials.push(SSRMaterial {
+            kind,
+            r: self.tint.r,
+            g: self.tint.g,
+            b: self.tint.b,
+            a: self.tint.a,
+            texture: info.texture.unwrap_or(0) as u32,
+        });
+        self.data
+            .params
+            .push(self.material.map(|(_, p)| p).unwrap_or_default());
+        self.data.tags.push(self.tag);
+        let translucent = self.translucent.unwrap_or_else(|| {
+            info.translucent
+                || self.tint.a < 1.0
+                || !matches!(self.blend, BlendMode::Alpha | BlendMode::Replace)
+                || info
+                    .texture
+                    .and_then(|t| self.core.textures.get(t))
+                    .is_some_and(|t| t.translucent)
+        });
+        self.data.object_info.push(SSRObjectInfo {
+            translucent,
+            blend: self.blend,
+            material: self.material.map(|(m, _)| m),
+            stencil,
+            stencil_ref,
+            clip: self.data.clips.last().copied(),
+            ..info
+        });
+    }
+    /// Let the renderer reorder objects with the same blend, material, mask and clip state
+    /// by texture and depth so more of them share a draw call. Off by default, as it changes
+    /// which of two overlapping objects at the same depth ends up on top.
+    pub fn set_batch_sorting(&mut self, sort: bool) {
+        self.data.sort = sort;
+    }
+    /// Clip everything drawn after this to rect, in pixels of the screen or render target.
+    /// Nested clip rects are intersected with the ones already pushed.
+    pub fn push_clip_rect(&mut self, rect: Rectangle) {
+        let clip = match self.data.clips.last() {
+            Some(outer) => match outer.overlapping_box(&rect) {
+                Some((top_left, bottom_right)) => {
+                    Rectangle::new2(top_left, bottom_right - top_left)
+                }
+                None => Rectangle::new2(rect.top_left, Vec2::ZERO),
+            },
+            None => rect,
+        };
+        self.data.clips.push(clip);
+    }
+    pub fn pop_clip_rect(&mut self) {
+        if self.data.clips.pop().is_none() {
+            warn!("pop_clip_rect called without a clip rect");
+        }
+    }
+    /// Everything drawn until end_mask only writes the mask, nothing shows up on screen.
+    /// Masks nest, an inner mask is limited to the inside of the one around it.
+    pub fn begin_mask(&mut self) {
+        self.data.masks.push(MaskLayer {
+            start: self.data.object_info.len(),
+            end: None,
+            mode: MaskMode::Inside,
+        });
+    }
+    /// Stop writing the mask, following draws are clipped to the inside or outside of it until pop_mask.
+    pub fn end_mask(&mut self, mode: MaskMode) {
+        let end = self.data.object_info.len();
+        match self.data.masks.last_mut() {
+            Some(mask) if mask.end.is_none() => {
+                mask.end = Some(end);
+                mask.mode = mode;
+            }
+            _ => warn!("end_mask called without begin_mask"),
+        }
+    }
+    /// Remove the innermost mask by drawing its shapes again, taking them back out of the stencil.
+    pub fn pop_mask(&mut self) {
+        let level = self.data.masks.len() as u32;
+        let mask = match self.data.masks.pop() {
+            Some(mask) => mask,
+            None => {
+                warn!("pop_mask called without a mask");
+                return;
+            }
+        };
+        let end = mask.end.unwrap_or(self.data.object_info.len());
+        for i in mask.start..end {
+            let info = self.data.object_info[i];
+            if info.stencil != StencilMode::Increment || info.stencil_ref != level - 1 {
+                continue;
+            }
+            self.data.transforms.push(self.data.transforms[i]);
+            self.data.materials.push(self.data.materials[i]);
+            self.data.params.push(self.data.params[i]);
+            self.data.tags.push(self.data.tags[i]);
+            self.data.object_info.push(SSRObjectInfo {
+                stencil: StencilMode::Decrement,
+                stencil_ref: level,
+                ..info
+            });
+        }
+    }
+    /// Everything drawn after this goes into target until end_target, cleared first when clear is given.
+    /// target should come from NGCore::create_render_target, and can be drawn from later in the same frame.
+    pub fn begin_target(&mut self, target: &Image, clear: Option<Color>) {
+        self.end_target();
+        let data = SSRRenderData {
+            sort: self.data.sort,
+            ..SSRRenderData::new()
+        };
+        self.screen = Some(std::mem::replace(&mut self.data, data));
+        self.target = Some((*target, clear));
+    }
+    pub fn end_target(&mut self) {
+        if let Some((target, clear)) = self.target.take() {
+            let screen = self.screen.take().unwrap_or_else(SSRRenderData::new);
+            let data = std::mem::replace(&mut self.data, screen);
+            self.core.render_target(0, Box::new(data), &target, clear);
+        }
+    }
     fn submit(&mut self) {
         self.core.render(0, Box::new(self.data.to_owned()));
     }
     pub fn render_image(&mut self, image: &Image, replace: bool) {
         self.core
             .render_image(0, Box::new(self.data.to_owned()), image, replace);
-        self.data = SSRRenderData::new();
+        self.data = SSRRenderData {
+            sort: self.data.sort,
+            ..SSRRenderData::new()
+        };
     }
 }
 impl Drop for ShapeGfx<'_> {
     fn drop(&mut self) {
+        self.end_target();
         self.submit();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::naga;
+
+    fn validate(source: &str) {
+        let module = naga::front::wgsl::parse_str(source).expect("Parse shader");
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::all(),
+        )
+        .validate(&module)
+        .expect("Validate shader");
+    }
+    #[test]
+    fn test_shaders_validate() {
+        validate(concat!(
+            include_str!("shape_shader.wgsl"),
+            include_str!("shape_fragment.wgsl")
+        ));
+        validate(concat!(
+            include_str!("shape_shader.wgsl"),
+            include_str!("shape_bindless.wgsl")
+        ));
+        validate(concat!(
+            include_str!("shape_shader.wgsl"),
+            include_str!("shape_pick.wgsl")
+        ));
+        validate(&format!(
+            "{}\n{}",
+            include_str!("shape_shader.wgsl"),
+            "@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let p = material_params(in);
+    return textureSample(tex, samp, in.tex) * p.a * sin(timer);
+}"
+        ));
+    }
+    #[test]
+    fn test_nested_mask_stencil() {
+        let mut data = SSRRenderData::new();
+        assert_eq!(data.stencil(), (StencilMode::Off, 0));
+        data.masks.push(MaskLayer {
+            start: 0,
+            end: None,
+            mode: MaskMode::Inside,
+        });
+        assert_eq!(data.stencil(), (StencilMode::Increment, 0));
+        data.masks[0].end = Some(0);
+        assert_eq!(data.stencil(), (StencilMode::Test, 1));
+        data.masks.push(MaskLayer {
+            start: 0,
+            end: None,
+            mode: MaskMode::Inside,
+        });
+        assert_eq!(data.stencil(), (StencilMode::Increment, 1));
+        data.masks[1].end = Some(0);
+        data.masks[1].mode = MaskMode::Outside;
+        assert_eq!(data.stencil(), (StencilMode::Test, 1));
+    }
+    #[test]
+    fn test_scissor_rect() {
+        assert_eq!(scissor_rect(None, (800, 600)), Some((0, 0, 800, 600)));
+        let clip = Rectangle::new(-10, 20, 100, 1000);
+        assert_eq!(scissor_rect(Some(clip), (800, 600)), Some((0, 20, 90, 580)));
+        let outside = Rectangle::new(900, 0, 10, 10);
+        assert_eq!(scissor_rect(Some(outside), (800, 600)), None);
+    }
+    #[test]
+    fn test_batch_vertices() {
+        let mut data = SSRRenderData::new();
+        let object = |texture, start: u32| SSRObjectInfo {
+            bo_slot: None,
+            texture,
+            start_index: start,
+            end_index: start + 3,
+            start_vertice: start,
+            end_vertice: start + 3,
+            depth: 0.0,
+            translucent: false,
+            blend: BlendMode::default(),
+            material: None,
+            stencil: StencilMode::Off,
+            stencil_ref: 0,
+            clip: None,
+            bindless: false,
+        };
+        for start in [0, 3, 6] {
+            data.vertices
+                .extend([<Vertex as bytemuck::Zeroable>::zeroed(); 3]);
+            data.indices.extend([0, 1, 2]);
+            data.object_info
+                .push(object(Some(start as usize % 2), start));
+        }
+        let mut objects = (0..)
+            .zip(data.object_info.iter().copied())
+            .collect::<Vec<_>>();
+        sort_objects(&mut objects);
+        assert_eq!(
+            objects.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            [0, 2, 1]
+        );
+        let (vertices, indices, ids) = batch_vertices(&mut objects, &data);
+        assert_eq!(vertices.len(), 9);
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(ids, [0, 0, 0, 2, 2, 2, 1, 1, 1]);
+        assert_eq!(objects[0].1.end_index, objects[1].1.start_index);
+    }
+    #[test]
+    fn test_sprite_quad() {
+        let image = Image::default().sub_image(Vec2::ZERO, Vec2::new(32, 16));
+        let params = SpriteParams {
+            pos: Vec2::new(100, 100),
+            origin: Vec2::new(16, 8),
+            scale: Vec2::new(2, 2),
+            flip_x: true,
+            sub_rect: Some(Rectangle::new(16, 0, 16, 16)),
+            ..Default::default()
+        };
+        let (vertices, indices) = sprite_quad(&image, &params);
+        assert_eq!((vertices[0].x, vertices[0].y), (68.0, 84.0));
+        assert_eq!((vertices[2].x, vertices[2].y), (100.0, 116.0));
+        assert_eq!(indices, [2, 1, 0, 3, 2, 0]);
+    }
+    #[test]
+    fn test_pick_tag() {
+        let tags = [None, Some(7)];
+        assert_eq!(pick_tag(0, &tags), None);
+        assert_eq!(pick_tag(1, &tags), Some(Tag::Object(0)));
+        assert_eq!(pick_tag(2, &tags), Some(Tag::User(7)));
+    }
+
+    /// Blends one channel the way the GPU would, after the fragment shader's premultiply.
+    fn blend_channel(mode: BlendMode, src: f32, alpha: f32, dst: f32) -> f32 {
+        use wgpu::BlendFactor as F;
+        let src = if mode.premultiplied() {
+            src * alpha
+        } else {
+            src
+        };
+        let factor = |f| match f {
+            F::Zero => 0.0,
+            F::One => 1.0,
+            F::Src => src,
+            F::OneMinusSrc => 1.0 - src,
+            F::SrcAlpha => alpha,
+            F::OneMinusSrcAlpha => 1.0 - alpha,
+            F::Dst => dst,
+            _ => unimplemented!(),
+        };
+        let color = mode.blend_state().color;
+        assert_eq!(color.operation, wgpu::BlendOperation::Add);
+        src * factor(color.src_factor) + dst * factor(color.dst_factor)
+    }
+
+    #[test]
+    fn test_blend_modes_weigh_alpha() {
+        let cases = [
+            // Transparent texels leave the frame alone whatever their color.
+            (BlendMode::Multiply, 1.0, 0.0, 0.4, 0.4),
+            (BlendMode::Screen, 1.0, 0.0, 0.4, 0.4),
+            (BlendMode::Multiply, 0.2, 1.0, 0.5, 0.1),
+            (BlendMode::Screen, 0.2, 1.0, 0.5, 0.6),
+            // Half transparent texels go halfway to the opaque result.
+            (BlendMode::Multiply, 1.0, 0.5, 0.4, 0.4),
+            (BlendMode::Multiply, 0.0, 0.5, 0.4, 0.2),
+            (BlendMode::Screen, 1.0, 0.5, 0.4, 0.7),
+        ];
+        for (mode, src, alpha, dst, expected) in cases {
+            let result = blend_channel(mode, src, alpha, dst);
+            assert!((result - expected).abs() < 1e-6, "{mode:?} gave {result}");
+        }
+    }
+
+    #[test]
+    fn test_order_translucent() {
+        let object = |translucent, depth, stencil| SSRObjectInfo {
+            bo_slot: None,
+            texture: None,
+            start_index: 0,
+            end_index: 0,
+            start_vertice: 0,
+            end_vertice: 0,
+            depth,
+            translucent,
+            blend: BlendMode::default(),
+            material: None,
+            stencil,
+            stencil_ref: 0,
+            clip: None,
+            bindless: false,
+        };
+        let mut objects = (0..)
+            .zip([
+                object(true, 5.0, StencilMode::Off),
+                object(false, 0.0, StencilMode::Off),
+                object(true, 1.0, StencilMode::Off),
+                object(false, 0.0, StencilMode::Increment),
+                object(true, 0.0, StencilMode::Test),
+                object(false, 9.0, StencilMode::Test),
+            ])
+            .collect::<Vec<_>>();
+        order_translucent(&mut objects);
+        let order = objects.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(order, [1, 2, 0, 3, 5, 4]);
+
+        let mut objects = (0..)
+            .zip([
+                object(true, 0.0, StencilMode::Off),
+                object(false, 0.0, StencilMode::Off),
+                object(true, 0.0, StencilMode::Off),
+            ])
+            .collect::<Vec<_>>();
+        order_translucent(&mut objects);
+        let order = objects.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(order, [0, 1, 2]);
+
+        // Sorting groups opaque objects by texture, that survives ordering the translucent ones.
+        let textured = |texture, depth| SSRObjectInfo {
+            texture: Some(texture),
+            ..object(false, depth, StencilMode::Off)
+        };
+        let mut objects = (0..)
+            .zip([
+                textured(1, 2.0),
+                textured(2, 1.0),
+                object(true, 0.5, StencilMode::Off),
+                textured(1, 0.0),
+            ])
+            .collect::<Vec<_>>();
+        sort_objects(&mut objects);
+        order_translucent(&mut objects);
+        let order = objects.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(order, [3, 0, 1, 2]);
+    }
+}
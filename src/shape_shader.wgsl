@@ -18,6 +18,8 @@ struct VertexInput {
     @location(0) pos: vec3<f32>,
     @location(1) tex: vec2<f32>,
     @location(2) color: vec4<f32>,
+    // Object index of batched vertices, zero for buffered meshes drawn as their own instance.
+    @location(3) object: u32,
 }
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
@@ -25,18 +27,32 @@ struct VertexOutput {
     @location(1) @interpolate(linear) color: vec4<f32>,
     @location(2) kind: i32,
     @location(3) tint:  vec4<f32>,
+    @location(4) inst: u32,
+    @location(5) @interpolate(flat) texture: u32,
 }
 @group(0) @binding(0)
 var<uniform> screen: vec2<f32>;
 @group(0) @binding(1)
 var<uniform> timer: f32;
 
+struct View {
+    offset: vec2<f32>,
+    origin: vec2<f32>,
+    zoom: f32,
+    rotation: f32,
+}
+@group(0) @binding(2)
+var<uniform> view: View;
+
 struct Transform {
     x: f32,
     y: f32,
     a: f32,
     rx: f32,
     ry: f32,
+    camera: u32,
+    // 2D affine from the ShapeGfx transform stack, rows of a 2x3 matrix.
+    m: array<f32, 6>,
 }
 struct Material {
     kind: i32,
@@ -44,6 +60,7 @@ struct Material {
     g: f32,
     b: f32,
     a: f32,
+    texture: u32,
 }
 
 @group(1) @binding(0)
@@ -52,6 +69,20 @@ var<storage,read> transforms: array<Transform>;
 @group(1) @binding(1)
 var<storage,read> materials: array<Material>;
 
+// User parameters of custom materials, one block per draw. Read them with material_params(in).
+struct MaterialParams {
+    a: vec4<f32>,
+    b: vec4<f32>,
+    c: vec4<f32>,
+    d: vec4<f32>,
+}
+@group(1) @binding(2)
+var<storage,read> params: array<MaterialParams>;
+
+fn material_params(in: VertexOutput) -> MaterialParams {
+    return params[in.inst];
+}
+
 @group(2) @binding(0)
 var tex: texture_2d<f32>;
 
@@ -59,14 +90,21 @@ var tex: texture_2d<f32>;
 var samp: sampler;
 
 @vertex
-fn vs_main(@builtin(vertex_index) index: u32, in: VertexInput, @builtin(instance_index) inst: u32) -> VertexOutput {
+fn vs_main(@builtin(vertex_index) index: u32, in: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+    let inst = instance + in.object;
     let a = transforms[inst].a;
     let rotation = mat2x2<f32>(cos(a),-sin(a),sin(a),cos(a));
     let offset = vec2<f32>(transforms[inst].rx,transforms[inst].ry);
     var raw_pos = (in.pos.xy - offset) * rotation;
-    var pos = ((raw_pos + offset) / screen) * 2.0;
-    var trans = ((vec2<f32>((transforms[inst].x),(transforms[inst].y)) / screen) - 0.5) * 2.0 ;
-    var output = pos + trans;
+    var world = raw_pos + offset + vec2<f32>(transforms[inst].x,transforms[inst].y);
+    let m = transforms[inst].m;
+    world = vec2<f32>(m[0] * world.x + m[1] * world.y + m[2], m[3] * world.x + m[4] * world.y + m[5]);
+    if transforms[inst].camera != 0u {
+        let r = view.rotation;
+        let view_rotation = mat2x2<f32>(cos(r),-sin(r),sin(r),cos(r));
+        world = ((world - view.offset - view.origin) * view_rotation) * view.zoom + view.origin;
+    }
+    var output = ((world / screen) - 0.5) * 2.0;
     output = vec2<f32>(output.x,output.y * -1.0);
 
     var out: VertexOutput;
@@ -75,11 +113,7 @@ fn vs_main(@builtin(vertex_index) index: u32, in: VertexInput, @builtin(instance
     out.color = in.color;
     out.kind = materials[inst].kind;
     out.tint = vec4<f32>(materials[inst].r,materials[inst].g,materials[inst].b,materials[inst].a);
+    out.inst = inst;
+    out.texture = materials[inst].texture;
     return out;
 }
-
-@fragment
-fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return in.color * textureSample(tex,samp,in.tex) * in.tint;
-}
-
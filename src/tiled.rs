@@ -0,0 +1,810 @@
+use crate::core::{Image, NGCore, NGError};
+use crate::math::{vec2, Vec2};
+use crate::mesh::Polygon;
+use crate::tilemap::Tilemap;
+use crate::util::{slice_image, PathBuilder, PathData, Rectangle};
+use roxmltree::Node;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Tile ids in layers carry flip flags in their top bits, they're cleared when loading.
+const FLIP_FLAGS: u32 = 0xF000_0000;
+
+/// Custom properties set in Tiled.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Property {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Strings, and also colors and files as Tiled writes them.
+    String(String),
+    /// The id of another object in the map.
+    Object(u32),
+}
+pub type Properties = HashMap<String, Property>;
+
+/// A map exported by the Tiled editor, as JSON (.tmj/.json) or XML (.tmx).
+#[derive(Clone, Debug)]
+pub struct TiledMap {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: Vec2,
+    pub tilesets: Vec<TiledTileset>,
+    /// In drawing order, group layers are flattened.
+    pub layers: Vec<TiledLayer>,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TiledTileset {
+    pub first_gid: u32,
+    pub name: String,
+    /// The tileset image, None for collections of single images.
+    pub image: Option<PathBuf>,
+    pub tile_size: Vec2,
+    pub columns: u32,
+    pub tile_count: u32,
+    pub margin: f32,
+    pub spacing: f32,
+    /// Images of tiles in an image collection tileset, by local tile id.
+    pub tile_images: HashMap<u32, PathBuf>,
+    /// Frames as local tile id and duration in milliseconds, by local tile id.
+    pub animations: HashMap<u32, Vec<(u32, f32)>>,
+    pub tile_properties: HashMap<u32, Properties>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TiledLayer {
+    Tiles(TiledTileLayer),
+    Objects(TiledObjectLayer),
+}
+
+#[derive(Clone, Debug)]
+pub struct TiledTileLayer {
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    /// Global tile ids row by row, None where there's no tile.
+    pub tiles: Vec<Option<u32>>,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug)]
+pub struct TiledObjectLayer {
+    pub name: String,
+    pub objects: Vec<TiledObject>,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+#[derive(Clone, Debug)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    /// The object's class, called type before Tiled 1.9.
+    pub class: String,
+    pub shape: TiledShape,
+    /// In degrees clockwise around the object's position, not applied to the shape.
+    pub rotation: f32,
+    pub visible: bool,
+    pub properties: Properties,
+}
+
+/// Object geometry in map pixels.
+#[derive(Clone, Debug)]
+pub enum TiledShape {
+    Rectangle(Rectangle),
+    /// The bounding box of the ellipse.
+    Ellipse(Rectangle),
+    Point(Vec2),
+    Polygon(Polygon),
+    Polyline(PathData),
+    /// A tile placed as an object, rect is where it's drawn.
+    Tile {
+        gid: u32,
+        rect: Rectangle,
+    },
+}
+
+/// Load a Tiled map, picking the format from the extension. Tileset and image paths are
+/// resolved relative to the map.
+pub fn load_tiled<P: AsRef<Path>>(file: P) -> Result<TiledMap, NGError> {
+    let path = file.as_ref();
+    let text = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("tmx") => TiledMap::from_tmx(&text, dir),
+        Some("tmj") | Some("json") => TiledMap::from_json(&text, dir),
+        _ => Err(NGError::UnsupportedFile(path.to_path_buf())),
+    }
+}
+
+fn map_error(message: impl Into<String>) -> NGError {
+    NGError::MapError(message.into())
+}
+
+fn gid(raw: u32) -> Option<u32> {
+    Some(raw & !FLIP_FLAGS).filter(|&gid| gid != 0)
+}
+
+impl TiledMap {
+    pub fn from_json(text: &str, dir: &Path) -> Result<Self, NGError> {
+        let map: Value = serde_json::from_str(text)?;
+        if map["infinite"].as_bool() == Some(true) {
+            return Err(map_error("Infinite maps aren't supported"));
+        }
+        let tilesets = map["tilesets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|ts| {
+                let first_gid = json_u32(ts, "firstgid");
+                let tileset = match ts["source"].as_str() {
+                    Some(source) => load_tileset(&dir.join(source))?,
+                    None => json_tileset(ts, dir),
+                };
+                Ok(TiledTileset {
+                    first_gid,
+                    ..tileset
+                })
+            })
+            .collect::<Result<Vec<_>, NGError>>()?;
+        let mut layers = vec![];
+        json_layers(&map["layers"], &mut layers)?;
+        Ok(Self {
+            width: json_u32(&map, "width") as usize,
+            height: json_u32(&map, "height") as usize,
+            tile_size: vec2(json_f32(&map, "tilewidth"), json_f32(&map, "tileheight")),
+            tilesets,
+            layers,
+            properties: json_properties(&map["properties"]),
+        })
+    }
+    pub fn from_tmx(text: &str, dir: &Path) -> Result<Self, NGError> {
+        let doc = roxmltree::Document::parse(text)?;
+        let map = doc.root_element();
+        if map.attribute("infinite") == Some("1") {
+            return Err(map_error("Infinite maps aren't supported"));
+        }
+        let tilesets = children(map, "tileset")
+            .map(|ts| {
+                let first_gid = xml_u32(ts, "firstgid");
+                let tileset = match ts.attribute("source") {
+                    Some(source) => load_tileset(&dir.join(source))?,
+                    None => xml_tileset(ts, dir),
+                };
+                Ok(TiledTileset {
+                    first_gid,
+                    ..tileset
+                })
+            })
+            .collect::<Result<Vec<_>, NGError>>()?;
+        let mut layers = vec![];
+        xml_layers(map, &mut layers)?;
+        Ok(Self {
+            width: xml_u32(map, "width") as usize,
+            height: xml_u32(map, "height") as usize,
+            tile_size: vec2(xml_f32(map, "tilewidth"), xml_f32(map, "tileheight")),
+            tilesets,
+            layers,
+            properties: xml_properties(map),
+        })
+    }
+    /// The tileset a global tile id belongs to, with the id local to it.
+    pub fn tileset(&self, gid: u32) -> Option<(&TiledTileset, u32)> {
+        self.tilesets
+            .iter()
+            .rev()
+            .find(|ts| ts.first_gid <= gid)
+            .map(|ts| (ts, gid - ts.first_gid))
+    }
+    pub fn tile_properties(&self, gid: u32) -> Option<&Properties> {
+        self.tileset(gid)
+            .and_then(|(ts, id)| ts.tile_properties.get(&id))
+    }
+    /// Load every tileset, the images are indexed by global tile id. Id 0 means no tile,
+    /// it holds a default image so the ids line up.
+    pub fn load_tiles(&self, core: &mut NGCore) -> Result<Vec<Image>, NGError> {
+        let mut tiles = vec![Image::default()];
+        for ts in self.tilesets.iter() {
+            tiles.resize(ts.first_gid as usize, Image::default());
+            match &ts.image {
+                Some(path) => {
+                    let image = core.load_image(path, true)?;
+                    tiles.extend(tileset_images(ts, &image));
+                }
+                None => {
+                    for path in collection_paths(ts) {
+                        tiles.push(match path {
+                            Some(path) => core.load_image(path, true)?,
+                            None => Image::default(),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(tiles)
+    }
+    /// A Tilemap with one layer per tile layer and the tileset animations, tiles are global tile ids.
+    pub fn tilemap(&self, core: &mut NGCore) -> Result<Tilemap, NGError> {
+        let mut tilemap = Tilemap::new(self.load_tiles(core)?, self.tile_size);
+        for layer in self.layers.iter() {
+            if let TiledLayer::Tiles(tiles) = layer {
+                let index = tilemap.add_layer(tiles.width, tiles.height);
+                for (i, tile) in tiles.tiles.iter().enumerate() {
+                    tilemap.set_tile(index, i % tiles.width, i / tiles.width, *tile);
+                }
+                if let Some(layer) = tilemap.layer_mut(index) {
+                    layer.visible = tiles.visible;
+                }
+            }
+        }
+        for ts in self.tilesets.iter() {
+            for (id, frames) in ts.animations.iter() {
+                let (frames, times) = frames
+                    .iter()
+                    .map(|(frame, time)| (frame + ts.first_gid, *time))
+                    .unzip();
+                tilemap.animate_tile_timed(id + ts.first_gid, frames, times);
+            }
+        }
+        Ok(tilemap)
+    }
+}
+
+/// Image paths of a collection tileset by local id. Ids can have gaps where tiles were deleted,
+/// so they go up to the largest id rather than the tile count.
+fn collection_paths(ts: &TiledTileset) -> Vec<Option<&PathBuf>> {
+    let end = ts.tile_images.keys().max().map_or(0, |id| id + 1);
+    (0..end).map(|id| ts.tile_images.get(&id)).collect()
+}
+
+/// Tiles of a tileset image in local id order. slice_image goes column by column, Tiled row by row.
+fn tileset_images(ts: &TiledTileset, image: &Image) -> Vec<Image> {
+    let columns = ts.columns.max(1);
+    let (margin, spacing) = (ts.margin, ts.spacing);
+    let sliced = (margin == 0.0 && spacing == 0.0).then(|| slice_image(image, ts.tile_size));
+    let rows = (image.size().y / ts.tile_size.y).floor() as u32;
+    (0..ts.tile_count)
+        .map(|id| {
+            let (column, row) = (id % columns, id / columns);
+            match &sliced {
+                Some(sliced) => sliced
+                    .get((column * rows + row) as usize)
+                    .copied()
+                    .unwrap_or_default(),
+                None => image.sub_image(
+                    vec2(
+                        margin + column as f32 * (ts.tile_size.x + spacing),
+                        margin + row as f32 * (ts.tile_size.y + spacing),
+                    ),
+                    ts.tile_size,
+                ),
+            }
+        })
+        .collect()
+}
+
+/// An external tileset, .tsx or JSON.
+fn load_tileset(path: &Path) -> Result<TiledTileset, NGError> {
+    let text = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("tsx") => {
+            let doc = roxmltree::Document::parse(&text)?;
+            Ok(xml_tileset(doc.root_element(), dir))
+        }
+        _ => Ok(json_tileset(&serde_json::from_str(&text)?, dir)),
+    }
+}
+
+fn object_shape(pos: Vec2, size: Vec2, gid: Option<u32>, kind: ObjectKind) -> TiledShape {
+    match (gid, kind) {
+        // Tile objects are placed by their bottom left corner.
+        (Some(gid), _) => TiledShape::Tile {
+            gid,
+            rect: Rectangle::new2(pos - vec2(0.0, size.y), size),
+        },
+        (None, ObjectKind::Point) => TiledShape::Point(pos),
+        (None, ObjectKind::Ellipse) => TiledShape::Ellipse(Rectangle::new2(pos, size)),
+        (None, ObjectKind::Polygon(points)) => {
+            let points = points.iter().map(|p| pos + *p).collect::<Vec<_>>();
+            let edges = (0..points.len())
+                .map(|i| (i, (i + 1) % points.len()))
+                .collect();
+            TiledShape::Polygon(Polygon { points, edges })
+        }
+        (None, ObjectKind::Polyline(points)) => {
+            let mut path = PathBuilder::default();
+            for (i, p) in points.iter().enumerate() {
+                if i == 0 {
+                    path.move_to(pos + *p);
+                } else {
+                    path.line_to(pos + *p);
+                }
+            }
+            path.close_path(false);
+            TiledShape::Polyline(path.build())
+        }
+        (None, ObjectKind::Rectangle) => TiledShape::Rectangle(Rectangle::new2(pos, size)),
+    }
+}
+
+enum ObjectKind {
+    Rectangle,
+    Ellipse,
+    Point,
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>),
+}
+
+fn json_f32(v: &Value, key: &str) -> f32 {
+    v[key].as_f64().unwrap_or(0.0) as f32
+}
+fn json_u32(v: &Value, key: &str) -> u32 {
+    v[key].as_u64().unwrap_or(0) as u32
+}
+fn json_str(v: &Value, key: &str) -> String {
+    v[key].as_str().unwrap_or_default().to_string()
+}
+
+fn json_properties(v: &Value) -> Properties {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .map(|p| {
+            let value = &p["value"];
+            let property = match p["type"].as_str().unwrap_or("string") {
+                "bool" => Property::Bool(value.as_bool().unwrap_or_default()),
+                "int" => Property::Int(value.as_i64().unwrap_or_default()),
+                "float" => Property::Float(value.as_f64().unwrap_or_default()),
+                "object" => Property::Object(value.as_u64().unwrap_or_default() as u32),
+                _ => Property::String(value.as_str().unwrap_or_default().to_string()),
+            };
+            (json_str(p, "name"), property)
+        })
+        .collect()
+}
+
+fn json_tileset(ts: &Value, dir: &Path) -> TiledTileset {
+    let mut tileset = TiledTileset {
+        first_gid: json_u32(ts, "firstgid"),
+        name: json_str(ts, "name"),
+        image: ts["image"].as_str().map(|image| dir.join(image)),
+        tile_size: vec2(json_f32(ts, "tilewidth"), json_f32(ts, "tileheight")),
+        columns: json_u32(ts, "columns"),
+        tile_count: json_u32(ts, "tilecount"),
+        margin: json_f32(ts, "margin"),
+        spacing: json_f32(ts, "spacing"),
+        ..Default::default()
+    };
+    for tile in ts["tiles"].as_array().into_iter().flatten() {
+        let id = json_u32(tile, "id");
+        if let Some(image) = tile["image"].as_str() {
+            tileset.tile_images.insert(id, dir.join(image));
+        }
+        if let Some(frames) = tile["animation"].as_array() {
+            let frames = frames
+                .iter()
+                .map(|f| (json_u32(f, "tileid"), json_f32(f, "duration")))
+                .collect();
+            tileset.animations.insert(id, frames);
+        }
+        let properties = json_properties(&tile["properties"]);
+        if !properties.is_empty() {
+            tileset.tile_properties.insert(id, properties);
+        }
+    }
+    tileset
+}
+
+fn json_points(v: &Value) -> Vec<Vec2> {
+    v.as_array()
+        .into_iter()
+        .flatten()
+        .map(|p| vec2(json_f32(p, "x"), json_f32(p, "y")))
+        .collect()
+}
+
+fn check_layer_size(name: &str, width: usize, height: usize, tiles: usize) -> Result<(), NGError> {
+    if width * height != tiles {
+        return Err(map_error(format!(
+            "Layer {name} is {width}x{height} but has {tiles} tiles"
+        )));
+    }
+    Ok(())
+}
+
+fn json_layers(v: &Value, layers: &mut Vec<TiledLayer>) -> Result<(), NGError> {
+    for layer in v.as_array().into_iter().flatten() {
+        let name = json_str(layer, "name");
+        let visible = layer["visible"].as_bool().unwrap_or(true);
+        let properties = json_properties(&layer["properties"]);
+        match layer["type"].as_str() {
+            Some("tilelayer") => {
+                let tiles = layer["data"]
+                    .as_array()
+                    .ok_or_else(|| map_error(format!("Layer {name} isn't saved as CSV")))?
+                    .iter()
+                    .map(|t| gid(t.as_u64().unwrap_or(0) as u32))
+                    .collect::<Vec<_>>();
+                let width = json_u32(layer, "width") as usize;
+                let height = json_u32(layer, "height") as usize;
+                check_layer_size(&name, width, height, tiles.len())?;
+                layers.push(TiledLayer::Tiles(TiledTileLayer {
+                    name,
+                    width,
+                    height,
+                    tiles,
+                    visible,
+                    properties,
+                }));
+            }
+            Some("objectgroup") => {
+                let objects = layer["objects"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(json_object)
+                    .collect();
+                layers.push(TiledLayer::Objects(TiledObjectLayer {
+                    name,
+                    objects,
+                    visible,
+                    properties,
+                }));
+            }
+            Some("group") => json_layers(&layer["layers"], layers)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn json_object(o: &Value) -> TiledObject {
+    let kind = if o["point"].as_bool() == Some(true) {
+        ObjectKind::Point
+    } else if o["ellipse"].as_bool() == Some(true) {
+        ObjectKind::Ellipse
+    } else if o["polygon"].is_array() {
+        ObjectKind::Polygon(json_points(&o["polygon"]))
+    } else if o["polyline"].is_array() {
+        ObjectKind::Polyline(json_points(&o["polyline"]))
+    } else {
+        ObjectKind::Rectangle
+    };
+    let class = o["class"]
+        .as_str()
+        .or(o["type"].as_str())
+        .unwrap_or_default();
+    TiledObject {
+        id: json_u32(o, "id"),
+        name: json_str(o, "name"),
+        class: class.to_string(),
+        shape: object_shape(
+            vec2(json_f32(o, "x"), json_f32(o, "y")),
+            vec2(json_f32(o, "width"), json_f32(o, "height")),
+            o["gid"].as_u64().and_then(|g| gid(g as u32)),
+            kind,
+        ),
+        rotation: json_f32(o, "rotation"),
+        visible: o["visible"].as_bool().unwrap_or(true),
+        properties: json_properties(&o["properties"]),
+    }
+}
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |n| n.has_tag_name(name))
+}
+fn xml_f32(node: Node, key: &str) -> f32 {
+    node.attribute(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0.0)
+}
+fn xml_u32(node: Node, key: &str) -> u32 {
+    node.attribute(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+fn xml_str(node: Node, key: &str) -> String {
+    node.attribute(key).unwrap_or_default().to_string()
+}
+fn xml_visible(node: Node) -> bool {
+    node.attribute("visible") != Some("0")
+}
+
+fn xml_properties(node: Node) -> Properties {
+    children(node, "properties")
+        .flat_map(|properties| children(properties, "property"))
+        .map(|p| {
+            // Multi-line strings are stored as text instead of a value attribute.
+            let value = p.attribute("value").or(p.text()).unwrap_or_default();
+            let property = match p.attribute("type").unwrap_or("string") {
+                "bool" => Property::Bool(value == "true"),
+                "int" => Property::Int(value.parse().unwrap_or_default()),
+                "float" => Property::Float(value.parse().unwrap_or_default()),
+                "object" => Property::Object(value.parse().unwrap_or_default()),
+                _ => Property::String(value.to_string()),
+            };
+            (xml_str(p, "name"), property)
+        })
+        .collect()
+}
+
+fn xml_tileset(ts: Node, dir: &Path) -> TiledTileset {
+    let mut tileset = TiledTileset {
+        first_gid: xml_u32(ts, "firstgid"),
+        name: xml_str(ts, "name"),
+        image: children(ts, "image")
+            .next()
+            .and_then(|image| image.attribute("source"))
+            .map(|image| dir.join(image)),
+        tile_size: vec2(xml_f32(ts, "tilewidth"), xml_f32(ts, "tileheight")),
+        columns: xml_u32(ts, "columns"),
+        tile_count: xml_u32(ts, "tilecount"),
+        margin: xml_f32(ts, "margin"),
+        spacing: xml_f32(ts, "spacing"),
+        ..Default::default()
+    };
+    for tile in children(ts, "tile") {
+        let id = xml_u32(tile, "id");
+        if let Some(image) = children(tile, "image")
+            .next()
+            .and_then(|i| i.attribute("source"))
+        {
+            tileset.tile_images.insert(id, dir.join(image));
+        }
+        if let Some(animation) = children(tile, "animation").next() {
+            let frames = children(animation, "frame")
+                .map(|f| (xml_u32(f, "tileid"), xml_f32(f, "duration")))
+                .collect();
+            tileset.animations.insert(id, frames);
+        }
+        let properties = xml_properties(tile);
+        if !properties.is_empty() {
+            tileset.tile_properties.insert(id, properties);
+        }
+    }
+    tileset
+}
+
+fn xml_points(node: Node) -> Vec<Vec2> {
+    node.attribute("points")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|point| {
+            let (x, y) = point.split_once(',')?;
+            Some(vec2(x.parse::<f32>().ok()?, y.parse::<f32>().ok()?))
+        })
+        .collect()
+}
+
+fn xml_layers(node: Node, layers: &mut Vec<TiledLayer>) -> Result<(), NGError> {
+    for layer in node.children().filter(|n| n.is_element()) {
+        let name = xml_str(layer, "name");
+        let visible = xml_visible(layer);
+        let properties = xml_properties(layer);
+        match layer.tag_name().name() {
+            "layer" => {
+                let data = children(layer, "data")
+                    .next()
+                    .ok_or_else(|| map_error(format!("Layer {name} has no data")))?;
+                let tiles: Vec<_> = match data.attribute("encoding") {
+                    Some("csv") => data
+                        .text()
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|t| gid(t.trim().parse().unwrap_or(0)))
+                        .collect(),
+                    None => children(data, "tile")
+                        .map(|t| gid(xml_u32(t, "gid")))
+                        .collect(),
+                    Some(_) => {
+                        return Err(map_error(format!("Layer {name} isn't saved as CSV")));
+                    }
+                };
+                let width = xml_u32(layer, "width") as usize;
+                let height = xml_u32(layer, "height") as usize;
+                check_layer_size(&name, width, height, tiles.len())?;
+                layers.push(TiledLayer::Tiles(TiledTileLayer {
+                    name,
+                    width,
+                    height,
+                    tiles,
+                    visible,
+                    properties,
+                }));
+            }
+            "objectgroup" => {
+                let objects = children(layer, "object").map(xml_object).collect();
+                layers.push(TiledLayer::Objects(TiledObjectLayer {
+                    name,
+                    objects,
+                    visible,
+                    properties,
+                }));
+            }
+            "group" => xml_layers(layer, layers)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn xml_object(o: Node) -> TiledObject {
+    let child = |name| children(o, name).next();
+    let kind = if child("point").is_some() {
+        ObjectKind::Point
+    } else if child("ellipse").is_some() {
+        ObjectKind::Ellipse
+    } else if let Some(polygon) = child("polygon") {
+        ObjectKind::Polygon(xml_points(polygon))
+    } else if let Some(polyline) = child("polyline") {
+        ObjectKind::Polyline(xml_points(polyline))
+    } else {
+        ObjectKind::Rectangle
+    };
+    let class = o
+        .attribute("class")
+        .or(o.attribute("type"))
+        .unwrap_or_default();
+    TiledObject {
+        id: xml_u32(o, "id"),
+        name: xml_str(o, "name"),
+        class: class.to_string(),
+        shape: object_shape(
+            vec2(xml_f32(o, "x"), xml_f32(o, "y")),
+            vec2(xml_f32(o, "width"), xml_f32(o, "height")),
+            gid(xml_u32(o, "gid")),
+            kind,
+        ),
+        rotation: xml_f32(o, "rotation"),
+        visible: xml_visible(o),
+        properties: xml_properties(o),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON: &str = r#"{
+        "width": 2, "height": 2, "tilewidth": 16, "tileheight": 16, "infinite": false,
+        "properties": [{"name": "music", "type": "string", "value": "cave.ogg"}],
+        "tilesets": [{"firstgid": 1, "name": "terrain", "image": "terrain.png",
+            "tilewidth": 16, "tileheight": 16, "columns": 4, "tilecount": 16, "margin": 0, "spacing": 0,
+            "tiles": [{"id": 3, "animation": [{"tileid": 3, "duration": 100}, {"tileid": 4, "duration": 200}],
+                "properties": [{"name": "solid", "type": "bool", "value": true}]}]}],
+        "layers": [
+            {"type": "tilelayer", "name": "ground", "width": 2, "height": 2, "visible": true,
+                "data": [1, 0, 2147483652, 3]},
+            {"type": "objectgroup", "name": "things", "objects": [
+                {"id": 1, "name": "spawn", "type": "player", "x": 8, "y": 8, "point": true},
+                {"id": 2, "name": "", "x": 10, "y": 20, "polygon": [{"x": 0, "y": 0}, {"x": 10, "y": 0}, {"x": 0, "y": 10}],
+                    "properties": [{"name": "damage", "type": "int", "value": 5}]}]}
+        ]
+    }"#;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties><property name="music" value="cave.ogg"/></properties>
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="16" columns="4">
+  <image source="terrain.png" width="64" height="64"/>
+  <tile id="3">
+   <properties><property name="solid" type="bool" value="true"/></properties>
+   <animation><frame tileid="3" duration="100"/><frame tileid="4" duration="200"/></animation>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="2" height="2">
+  <data encoding="csv">
+1,0,
+2147483652,3
+</data>
+ </layer>
+ <objectgroup id="2" name="things">
+  <object id="1" name="spawn" type="player" x="8" y="8"><point/></object>
+  <object id="2" x="10" y="20">
+   <properties><property name="damage" type="int" value="5"/></properties>
+   <polygon points="0,0 10,0 0,10"/>
+  </object>
+ </objectgroup>
+</map>"#;
+
+    fn check(map: &TiledMap) {
+        assert_eq!((map.width, map.height), (2, 2));
+        assert_eq!(
+            map.properties.get("music"),
+            Some(&Property::String("cave.ogg".into()))
+        );
+        let ts = &map.tilesets[0];
+        assert_eq!(ts.image.as_deref(), Some(Path::new("maps/terrain.png")));
+        assert_eq!(ts.animations.get(&3), Some(&vec![(3, 100.0), (4, 200.0)]));
+        assert_eq!(
+            map.tile_properties(4).and_then(|p| p.get("solid")),
+            Some(&Property::Bool(true))
+        );
+        match &map.layers[0] {
+            TiledLayer::Tiles(layer) => {
+                assert_eq!(layer.tiles, [Some(1), None, Some(4), Some(3)])
+            }
+            _ => panic!("Expected a tile layer"),
+        }
+        match &map.layers[1] {
+            TiledLayer::Objects(layer) => {
+                assert_eq!(layer.objects[0].class, "player");
+                assert!(matches!(layer.objects[0].shape, TiledShape::Point(p) if p == vec2(8, 8)));
+                match &layer.objects[1].shape {
+                    TiledShape::Polygon(polygon) => {
+                        assert_eq!(polygon.points[1], vec2(20, 20));
+                        assert_eq!(polygon.edges[2], (2, 0));
+                    }
+                    _ => panic!("Expected a polygon"),
+                }
+                assert_eq!(
+                    layer.objects[1].properties.get("damage"),
+                    Some(&Property::Int(5))
+                );
+            }
+            _ => panic!("Expected an object layer"),
+        }
+    }
+
+    #[test]
+    fn test_tiled_json_and_tmx() {
+        check(&TiledMap::from_json(JSON, Path::new("maps")).expect("Parse JSON map"));
+        check(&TiledMap::from_tmx(TMX, Path::new("maps")).expect("Parse TMX map"));
+    }
+
+    #[test]
+    fn test_tileset_images_row_order() {
+        let image = Image::default().sub_image(Vec2::ZERO, vec2(64, 32));
+        let ts = TiledTileset {
+            tile_size: vec2(16, 16),
+            columns: 4,
+            tile_count: 8,
+            ..Default::default()
+        };
+        let tiles = tileset_images(&ts, &image);
+        assert_eq!(
+            tiles[1].sub_image.map(|(start, _)| start),
+            Some(vec2(16, 0))
+        );
+        assert_eq!(
+            tiles[4].sub_image.map(|(start, _)| start),
+            Some(vec2(0, 16))
+        );
+    }
+
+    #[test]
+    fn test_collection_paths_with_gaps() {
+        let ts = TiledTileset {
+            tile_count: 2,
+            tile_images: HashMap::from([(0, "a.png".into()), (5, "b.png".into())]),
+            ..Default::default()
+        };
+        let paths = collection_paths(&ts);
+        assert_eq!(paths.len(), 6);
+        assert_eq!(paths[0], Some(&PathBuf::from("a.png")));
+        assert_eq!(paths[1], None);
+        assert_eq!(paths[5], Some(&PathBuf::from("b.png")));
+    }
+
+    #[test]
+    fn test_layer_size_mismatch() {
+        let json = JSON.replace(r#""width": 2, "height": 2, "visible""#, r#""visible""#);
+        assert!(matches!(
+            TiledMap::from_json(&json, Path::new("maps")),
+            Err(NGError::MapError(_))
+        ));
+        let tmx = TMX.replace(r#"name="ground" width="2""#, r#"name="ground""#);
+        assert!(matches!(
+            TiledMap::from_tmx(&tmx, Path::new("maps")),
+            Err(NGError::MapError(_))
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::core::{Image, NGCore};
+use crate::math::{vec2, Vec2};
+use crate::mesh::{Mesh, MeshBuilder};
+use crate::shape_pipeline::{BufferedObjectID, ShapeGfx};
+use crate::util::{Animation, Camera, Rectangle};
+use std::collections::HashMap;
+
+/// A grid of tile indices into a tileset, drawn as buffered meshes of chunk_size by chunk_size tiles.
+/// Only chunks whose tiles changed are rebuilt, and chunks off camera aren't drawn.
+pub struct Tilemap {
+    tiles: Vec<Image>,
+    tile_size: Vec2,
+    chunk_size: usize,
+    layers: Vec<TileLayer>,
+    animations: HashMap<u32, Animation<u32>>,
+}
+
+pub struct TileLayer {
+    width: usize,
+    height: usize,
+    tiles: Vec<Option<u32>>,
+    depth: f32,
+    pub visible: bool,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Default)]
+struct Chunk {
+    buffer: Option<BufferedObjectID>,
+    dirty: bool,
+    empty: bool,
+    animated: bool,
+}
+
+impl TileLayer {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn depth(&self) -> f32 {
+        self.depth
+    }
+    /// The z of every tile in the layer, like ShapeGfx::set_depth.
+    pub fn set_depth(&mut self, depth: f32) {
+        self.depth = depth;
+        self.chunks.iter_mut().for_each(|chunk| chunk.dirty = true);
+    }
+    pub fn tile(&self, x: usize, y: usize) -> Option<u32> {
+        if x < self.width && y < self.height {
+            self.tiles[y * self.width + x]
+        } else {
+            None
+        }
+    }
+}
+
+impl Tilemap {
+    /// tiles is the tileset, usually from slice_image or create_texture_atlas, indexed by tile number.
+    pub fn new(tiles: Vec<Image>, tile_size: Vec2) -> Self {
+        Self {
+            tiles,
+            tile_size,
+            chunk_size: 16,
+            layers: vec![],
+            animations: HashMap::new(),
+        }
+    }
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+    pub fn tile_size(&self) -> Vec2 {
+        self.tile_size
+    }
+    /// Adds an empty layer drawn on top of the ones before it, returns its index.
+    pub fn add_layer(&mut self, width: usize, height: usize) -> usize {
+        let chunks = width.div_ceil(self.chunk_size) * height.div_ceil(self.chunk_size);
+        self.layers.push(TileLayer {
+            width,
+            height,
+            tiles: vec![None; width * height],
+            depth: 0.0,
+            visible: true,
+            chunks: (0..chunks)
+                .map(|_| Chunk {
+                    empty: true,
+                    ..Default::default()
+                })
+                .collect(),
+        });
+        self.layers.len() - 1
+    }
+    pub fn layers(&self) -> &[TileLayer] {
+        &self.layers
+    }
+    pub fn layer_mut(&mut self, layer: usize) -> Option<&mut TileLayer> {
+        self.layers.get_mut(layer)
+    }
+    pub fn tile(&self, layer: usize, x: usize, y: usize) -> Option<u32> {
+        self.layers.get(layer).and_then(|l| l.tile(x, y))
+    }
+    pub fn set_tile(&mut self, layer: usize, x: usize, y: usize, tile: Option<u32>) {
+        let chunk_size = self.chunk_size;
+        let layer = match self.layers.get_mut(layer) {
+            Some(layer) if x < layer.width && y < layer.height => layer,
+            _ => return,
+        };
+        let index = y * layer.width + x;
+        if layer.tiles[index] != tile {
+            layer.tiles[index] = tile;
+            let columns = layer.width.div_ceil(chunk_size);
+            layer.chunks[(y / chunk_size) * columns + x / chunk_size].dirty = true;
+        }
+    }
+    /// Replace tile with frames, each shown for frame_time milliseconds, wherever it's placed.
+    pub fn animate_tile(&mut self, tile: u32, frames: Vec<u32>, frame_time: f32) {
+        if frames.is_empty() {
+            self.animations.remove(&tile);
+        } else {
+            self.animations
+                .insert(tile, Animation::new(frames, frame_time));
+        }
+        self.mark_animated(|_| true);
+    }
+    /// Like animate_tile with its own time in milliseconds for each frame.
+    pub fn animate_tile_timed(&mut self, tile: u32, frames: Vec<u32>, frame_times: Vec<f32>) {
+        if frames.is_empty() {
+            self.animations.remove(&tile);
+        } else {
+            self.animations
+                .insert(tile, Animation::new_variable_timing(frames, frame_times));
+        }
+        self.mark_animated(|_| true);
+    }
+    /// Advance animated tiles, delta in seconds. Chunks showing a tile whose frame changed get rebuilt.
+    pub fn update(&mut self, delta: f32) {
+        let mut changed = false;
+        for animation in self.animations.values_mut() {
+            let frame = *animation.get_frame();
+            animation.update(delta);
+            changed |= *animation.get_frame() != frame;
+        }
+        if changed {
+            self.mark_animated(|chunk| chunk.animated);
+        }
+    }
+    fn mark_animated(&mut self, filter: impl Fn(&Chunk) -> bool) {
+        for chunk in self.layers.iter_mut().flat_map(|l| l.chunks.iter_mut()) {
+            if filter(chunk) {
+                chunk.dirty = true;
+            }
+        }
+    }
+    /// The tile shown for tile right now, following its animation.
+    fn shown(&self, tile: u32) -> u32 {
+        self.animations
+            .get(&tile)
+            .map_or(tile, |animation| *animation.get_frame())
+    }
+    /// The area of a chunk in the map, in pixels from the map's top left.
+    fn chunk_rect(&self, layer: &TileLayer, chunk: usize) -> Rectangle {
+        let columns = layer.width.div_ceil(self.chunk_size);
+        let (cx, cy) = (chunk % columns, chunk / columns);
+        let size = self.tile_size * self.chunk_size as f32;
+        Rectangle::new2(vec2(cx as f32 * size.x, cy as f32 * size.y), size)
+    }
+    fn build_chunk(&self, layer: &TileLayer, chunk: usize) -> (Mesh, bool) {
+        let columns = layer.width.div_ceil(self.chunk_size);
+        let (cx, cy) = (chunk % columns, chunk / columns);
+        let mut mb = MeshBuilder::default();
+        mb.set_depth(layer.depth);
+        let mut animated = false;
+        for y in cy * self.chunk_size..((cy + 1) * self.chunk_size).min(layer.height) {
+            for x in cx * self.chunk_size..((cx + 1) * self.chunk_size).min(layer.width) {
+                let tile = match layer.tile(x, y) {
+                    Some(tile) => tile,
+                    None => continue,
+                };
+                animated |= self.animations.contains_key(&tile);
+                if let Some(image) = self.tiles.get(self.shown(tile) as usize) {
+                    mb.set_image(image);
+                    mb.set_cursor(vec2(
+                        x as f32 * self.tile_size.x,
+                        y as f32 * self.tile_size.y,
+                    ));
+                    mb.rect(self.tile_size);
+                }
+            }
+        }
+        (mb.build(), animated)
+    }
+    /// Draw every visible layer with the map's top left at pos. With a camera, only chunks it can
+    /// see are drawn, ShapeGfx::set_camera should be set to the same camera.
+    pub fn draw(&mut self, g: &mut ShapeGfx, pos: Vec2, camera: Option<&Camera>) {
+        let view = camera.map(|camera| camera.visible_rect());
+        for l in 0..self.layers.len() {
+            if !self.layers[l].visible {
+                continue;
+            }
+            for c in 0..self.layers[l].chunks.len() {
+                if self.layers[l].chunks[c].dirty {
+                    self.rebuild_chunk(g.core(), l, c);
+                }
+                let layer = &self.layers[l];
+                let id = match &layer.chunks[c] {
+                    Chunk {
+                        empty: false,
+                        buffer: Some(id),
+                        ..
+                    } => *id,
+                    _ => continue,
+                };
+                let rect = self.chunk_rect(layer, c);
+                let rect = Rectangle::new2(rect.top_left + pos, rect.size());
+                if view.is_some_and(|view| view.overlapping_box(&rect).is_none()) {
+                    continue;
+                }
+                g.draw_buffer(id, pos);
+            }
+        }
+    }
+    fn rebuild_chunk(&mut self, core: &mut NGCore, layer: usize, chunk: usize) {
+        let (mesh, animated) = self.build_chunk(&self.layers[layer], chunk);
+        let empty = mesh.indices.is_empty();
+        let chunk = &mut self.layers[layer].chunks[chunk];
+        match (chunk.buffer, empty) {
+            (_, true) => {}
+            (Some(id), false) => {
+                core.update_buffer_object(id, &mesh);
+            }
+            (None, false) => chunk.buffer = Some(core.buffer_object(&mesh)),
+        }
+        chunk.dirty = false;
+        chunk.empty = empty;
+        chunk.animated = animated;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dirty_chunks() {
+        let mut map = Tilemap::new(vec![Image::default(); 4], vec2(16, 16)).with_chunk_size(4);
+        let layer = map.add_layer(10, 6);
+        assert_eq!(map.layers()[layer].chunks.len(), 3 * 2);
+        map.set_tile(layer, 5, 4, Some(2));
+        assert_eq!(map.tile(layer, 5, 4), Some(2));
+        let dirty = map.layers()[layer]
+            .chunks
+            .iter()
+            .map(|c| c.dirty)
+            .collect::<Vec<_>>();
+        assert_eq!(dirty, [false, false, false, false, true, false]);
+        let (mesh, animated) = map.build_chunk(&map.layers()[layer], 4);
+        assert_eq!(mesh.vertices.len(), 4);
+        assert!(!animated);
+        map.animate_tile(2, vec![2, 3], 100.0);
+        let (_, animated) = map.build_chunk(&map.layers()[layer], 4);
+        assert!(animated);
+    }
+}
@@ -1,7 +1,8 @@
-use crate::core::{Image, NGCore, NGError, TEXTURE_SIZE};
+use crate::core::{Image, ImageOptions, NGCore, NGError, TEXTURE_SIZE};
 use crate::math::{angle_vec2, vec2, Vec2};
 use crate::mesh::{rect_filled, FillStyle, FillStyleShorthand, MeshBuilder, Polygon};
 use crate::shape_pipeline::ShapeGfx;
+use crate::ViewUniform;
 use num_traits::{AsPrimitive, Inv};
 use rand::Rng;
 use rand_xorshift;
@@ -328,6 +329,9 @@ pub struct Camera {
     upper_bound: Vec2,
     screen_size: Vec2,
     bounded: bool,
+    zoom: f32,
+    rotation: f32,
+    origin: Vec2,
 }
 impl Camera {
     pub fn new(screen_size: Vec2) -> Self {
@@ -337,8 +341,66 @@ impl Camera {
             upper_bound: Vec2::ZERO,
             screen_size,
             bounded: false,
+            zoom: 1.0,
+            rotation: 0.0,
+            origin: screen_size / 2.0,
         }
     }
+    /// The world position shown at the top left of the screen, before zoom and rotation.
+    pub fn set_position(&mut self, pos: Vec2) {
+        self.offset = pos;
+    }
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom;
+    }
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+    /// The screen point zoom and rotation happen around, the center of the screen by default.
+    pub fn set_origin(&mut self, origin: Vec2) {
+        self.origin = origin;
+    }
+    pub fn origin(&self) -> Vec2 {
+        self.origin
+    }
+    pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+        let p = world - self.offset - self.origin;
+        let (sin, cos) = self.rotation.sin_cos();
+        let rotated = Vec2::new(p.x * cos - p.y * sin, p.x * sin + p.y * cos);
+        rotated * self.zoom + self.origin
+    }
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        let p = (screen - self.origin) / self.zoom;
+        let (sin, cos) = self.rotation.sin_cos();
+        let unrotated = Vec2::new(p.x * cos + p.y * sin, -p.x * sin + p.y * cos);
+        unrotated + self.origin + self.offset
+    }
+    /// The part of the world that's on screen, grown to an axis aligned box when rotated.
+    pub fn visible_rect(&self) -> Rectangle {
+        let corners = [
+            Vec2::ZERO,
+            Vec2::new(self.screen_size.x, 0.0),
+            self.screen_size,
+            Vec2::new(0.0, self.screen_size.y),
+        ]
+        .map(|corner| self.screen_to_world(corner));
+        let min = corners.iter().fold(corners[0], |min, c| {
+            Vec2::new(min.x.min(c.x), min.y.min(c.y))
+        });
+        let max = corners.iter().fold(corners[0], |max, c| {
+            Vec2::new(max.x.max(c.x), max.y.max(c.y))
+        });
+        Rectangle::new2(min, max - min)
+    }
+    pub(crate) fn view(&self) -> ViewUniform {
+        ViewUniform::new(self.offset, self.origin, self.zoom, self.rotation)
+    }
     pub fn set_bounds(&mut self, lower_bound: Vec2, upper_bound: Vec2) {
         self.bounded = true;
         self.lower_bound = lower_bound;
@@ -1041,6 +1103,96 @@ pub fn slice_image(image: &Image, size: Vec2) -> Vec<Image> {
     img_tiles
 }
 
+/// A CPU copy of an image's pixels for textures that change every frame, like painting, video
+/// frames or simulations. Edit the pixels, then upload sends only the area that changed. The
+/// image can't use mipmaps.
+pub struct StreamingImage {
+    image: Image,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+    /// Changed pixels as min and max corners, inclusive.
+    dirty: Option<((u32, u32), (u32, u32))>,
+}
+
+impl StreamingImage {
+    pub fn new(
+        core: &mut NGCore,
+        width: u32,
+        height: u32,
+        options: impl Into<ImageOptions>,
+    ) -> Self {
+        let pixels = vec![0; (width * height * 4) as usize];
+        let image = core.load_image_data(width, height, &pixels, options);
+        Self {
+            image,
+            width,
+            height,
+            pixels,
+            dirty: None,
+        }
+    }
+    pub fn image(&self) -> Image {
+        self.image
+    }
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    /// RGBA8 rows, top to bottom.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    /// All the pixels, the whole image is uploaded next time.
+    pub fn pixels_mut(&mut self) -> &mut [u8] {
+        if self.width > 0 && self.height > 0 {
+            self.dirty = Some(((0, 0), (self.width - 1, self.height - 1)));
+        }
+        &mut self.pixels
+    }
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        (x < self.width && y < self.height).then(|| {
+            let i = ((y * self.width + x) * 4) as usize;
+            [
+                self.pixels[i],
+                self.pixels[i + 1],
+                self.pixels[i + 2],
+                self.pixels[i + 3],
+            ]
+        })
+    }
+    pub fn set_pixel(&mut self, x: u32, y: u32, rgba: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let i = ((y * self.width + x) * 4) as usize;
+        self.pixels[i..i + 4].copy_from_slice(&rgba);
+        self.dirty = Some(match self.dirty {
+            Some((min, max)) => ((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))),
+            None => ((x, y), (x, y)),
+        });
+    }
+    /// Send the changed pixels to the texture, call once a frame before drawing the image.
+    pub fn upload(&mut self, core: &mut NGCore) -> Result<(), NGError> {
+        match self.take_dirty() {
+            Some((rect, data)) => core.update_image(&self.image, rect, &data),
+            None => Ok(()),
+        }
+    }
+    /// The changed area and its pixels, clearing it.
+    fn take_dirty(&mut self) -> Option<(Rectangle, Vec<u8>)> {
+        let ((x0, y0), (x1, y1)) = self.dirty.take()?;
+        let row = (x1 - x0 + 1) as usize * 4;
+        let data = (y0..=y1)
+            .flat_map(|y| {
+                let start = ((y * self.width + x0) * 4) as usize;
+                &self.pixels[start..start + row]
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        Some((Rectangle::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1), data))
+    }
+}
+
 pub struct Animation<T> {
     frame_time: Vec<f32>,
     frames: Vec<T>,
@@ -1104,3 +1256,51 @@ impl<T> Animation<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_streaming_image_dirty_rect() {
+        let mut image = StreamingImage {
+            image: Image::default(),
+            width: 4,
+            height: 3,
+            pixels: vec![0; 4 * 3 * 4],
+            dirty: None,
+        };
+        assert_eq!(image.pixel(u32::MAX, 0), None);
+        assert_eq!(image.pixel(0, 3), None);
+        image.set_pixel(4, 0, [9; 4]);
+        assert!(image.take_dirty().is_none());
+
+        image.set_pixel(1, 0, [1, 2, 3, 4]);
+        image.set_pixel(2, 1, [5, 6, 7, 8]);
+        assert_eq!(image.pixel(1, 0), Some([1, 2, 3, 4]));
+        let (rect, data) = image.take_dirty().unwrap();
+        assert_eq!(rect, Rectangle::new(1, 0, 2, 2));
+        assert_eq!(data, [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 5, 6, 7, 8]);
+        assert!(image.take_dirty().is_none());
+
+        image.pixels_mut();
+        let (rect, data) = image.take_dirty().unwrap();
+        assert_eq!(rect, Rectangle::new(0, 0, 4, 3));
+        assert_eq!(data.len(), 4 * 3 * 4);
+    }
+
+    #[test]
+    fn test_camera_round_trip() {
+        let mut camera = Camera::new(vec2(800, 600));
+        camera.set_position(vec2(100, 50));
+        camera.set_zoom(2.0);
+        camera.set_rotation(0.5);
+        let world = vec2(321, 123);
+        let back = camera.screen_to_world(camera.world_to_screen(world));
+        assert!((back - world).magnitude() < 0.001);
+        camera.set_rotation(0.0);
+        // The origin stays put while zooming.
+        assert_eq!(camera.world_to_screen(vec2(500, 350)), vec2(400, 300));
+        assert_eq!(camera.world_to_screen(vec2(510, 350)), vec2(420, 300));
+    }
+}